@@ -0,0 +1,106 @@
+use chrono::{DateTime, Duration, Local};
+use failure::{format_err, Error};
+
+use crate::catalog::Catalog;
+use crate::config::BudgetConfig;
+use crate::duration;
+use crate::pomodoro;
+use crate::toggl::TimeEntry;
+
+/// Returns the time budget of the task the entry belongs to.  A budget tag
+/// wins over the Toggl task estimate, which wins over the per-project budget.
+pub fn task_budget(
+    config: &BudgetConfig,
+    entry: &TimeEntry,
+    catalog: &Catalog,
+) -> Result<Option<Duration>, Error> {
+    for tag in &entry.tags {
+        if let Some(d) = tag
+            .strip_prefix(config.tag_prefix.as_str())
+            .and_then(duration::parse)
+        {
+            return Ok(Some(d));
+        }
+    }
+    if let Some(secs) = catalog
+        .task(entry.task_id)
+        .and_then(|x| x.estimated_seconds)
+    {
+        if secs > 0 {
+            return Ok(Some(Duration::seconds(secs)));
+        }
+    }
+    if let Some(project) = catalog.project_name(entry) {
+        if let Some(s) = config.projects.get(&project) {
+            return duration::parse(s)
+                .map(Some)
+                .ok_or_else(|| format_err!("invalid budget for {}: {}", project, s));
+        }
+    }
+    Ok(None)
+}
+
+pub fn is_same_task(a: &TimeEntry, b: &TimeEntry) -> bool {
+    if a.task_id.is_some() && b.task_id.is_some() {
+        return a.task_id == b.task_id;
+    }
+    a.description == b.description && a.project_id == b.project_id && a.tags == b.tags
+}
+
+/// Sums the seconds already spent today on the task of `entry` in other,
+/// stopped entries.  An entry running over the start of the day counts
+/// with the part after it; the part before went to the previous day.
+pub fn spent_today(entries: &[TimeEntry], entry: &TimeEntry, day_start: DateTime<Local>) -> i64 {
+    entries
+        .iter()
+        .filter(|x| x.id != entry.id && x.duration >= 0)
+        .filter(|x| is_same_task(x, entry))
+        .map(|x| {
+            let stop = x.stop.unwrap_or(x.start + Duration::seconds(x.duration));
+            (stop - x.start.max(day_start)).num_seconds().max(0)
+        })
+        .sum()
+}
+
+/// Returns when the running `entry` uses up today's budget of its task.
+pub fn finish_time(
+    budget: Duration,
+    entries: &[TimeEntry],
+    entry: &TimeEntry,
+    day_start_hour: u32,
+    now: DateTime<Local>,
+) -> DateTime<Local> {
+    let day_start = pomodoro::start_of_day(pomodoro::day_of(&now, day_start_hour), day_start_hour)
+        .unwrap_or(entry.start);
+    entry.start.max(day_start) + budget - Duration::seconds(spent_today(entries, entry, day_start))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn entry(id: u64, start: DateTime<Local>, secs: i64) -> TimeEntry {
+        let mut entry = TimeEntry::new(id, "write", None, &[], start);
+        if secs >= 0 {
+            entry.stop_at(start + Duration::seconds(secs));
+        }
+        entry
+    }
+
+    #[test]
+    fn budget_counts_the_part_after_the_day_start() {
+        let at = |h, m| Local.with_ymd_and_hms(2024, 5, 2, h, m, 0).unwrap();
+        let day_start = at(4, 0);
+        // 3:30-4:30 spans the 4:00 day start; 9:00-9:20 is all today.
+        let entries = vec![
+            entry(3, at(10, 0), -1),
+            entry(2, at(9, 0), 20 * 60),
+            entry(1, at(3, 30), 60 * 60),
+        ];
+        assert_eq!(spent_today(&entries, &entries[0], day_start), 50 * 60);
+
+        let finish = finish_time(Duration::hours(1), &entries, &entries[0], 4, at(10, 5));
+        assert_eq!(finish, at(10, 10));
+    }
+}
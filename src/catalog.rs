@@ -0,0 +1,205 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use chrono::{DateTime, Duration, Local};
+use failure::Error;
+use lazy_static::lazy_static;
+use log::warn;
+
+use crate::toggl::{Client, Project, Tag, Task, TimeEntry, Toggl, Workspace};
+
+/// Minutes after which a refresh that failed in part is retried.
+const RETRY_MIN: u32 = 5;
+
+/// Cached Toggl workspaces, projects, clients and tags, used to resolve the
+/// names that `/me/time_entries` leaves out.
+#[derive(Debug, Default)]
+pub struct Catalog {
+    pub default_workspace_id: Option<u64>,
+    pub workspaces: HashMap<u64, Workspace>,
+    pub projects: HashMap<u64, Project>,
+    pub clients: HashMap<u64, Client>,
+    pub tasks: HashMap<u64, Task>,
+    pub tags: HashMap<u64, Tag>,
+    /// When the last refresh was attempted, successful or not.
+    updated_at: Option<DateTime<Local>>,
+    /// Set when some parts failed in the last refresh.
+    incomplete: bool,
+}
+
+/// The parts of the catalog fetched in one refresh.  Parts that failed
+/// are `None` so the cached ones are kept.
+#[derive(Debug, Default)]
+pub struct CatalogUpdate {
+    pub default_workspace_id: Option<u64>,
+    pub workspaces: Option<HashMap<u64, Workspace>>,
+    pub projects: Option<HashMap<u64, Project>>,
+    pub clients: Option<HashMap<u64, Client>>,
+    pub tasks: Option<HashMap<u64, Task>>,
+    pub tags: Option<HashMap<u64, Tag>>,
+    pub failed: bool,
+}
+
+impl CatalogUpdate {
+    fn part<T>(&mut self, name: &str, result: Result<T, Error>) -> Option<T> {
+        match result {
+            Ok(x) => Some(x),
+            Err(e) => {
+                warn!("fetching {} failed: {}", name, e);
+                self.failed = true;
+                None
+            }
+        }
+    }
+}
+
+impl Catalog {
+    /// Fetches every part of the catalog from Toggl.  A failing endpoint,
+    /// such as `/me/tasks` on plans without tasks, only loses its part.
+    pub fn fetch(toggl: &Toggl) -> CatalogUpdate {
+        let mut u = CatalogUpdate::default();
+        u.default_workspace_id = u.part("user", toggl.me()).map(|x| x.default_workspace_id);
+        u.workspaces = u
+            .part("workspaces", toggl.workspaces())
+            .map(|x| x.into_iter().map(|x| (x.id, x)).collect());
+        u.projects = u
+            .part("projects", toggl.projects())
+            .map(|x| x.into_iter().map(|x| (x.id, x)).collect());
+        u.clients = u
+            .part("clients", toggl.clients())
+            .map(|x| x.into_iter().map(|x| (x.id, x)).collect());
+        u.tasks = u
+            .part("tasks", toggl.tasks())
+            .map(|x| x.into_iter().map(|x| (x.id, x)).collect());
+        u.tags = u
+            .part("tags", toggl.tags())
+            .map(|x| x.into_iter().map(|x| (x.id, x)).collect());
+        u
+    }
+
+    /// Replaces the parts that were fetched and records the attempt, so a
+    /// failing endpoint is retried after `RETRY_MIN` instead of every tick.
+    pub fn apply(&mut self, update: CatalogUpdate) {
+        if update.default_workspace_id.is_some() {
+            self.default_workspace_id = update.default_workspace_id;
+        }
+        if let Some(x) = update.workspaces {
+            self.workspaces = x;
+        }
+        if let Some(x) = update.projects {
+            self.projects = x;
+        }
+        if let Some(x) = update.clients {
+            self.clients = x;
+        }
+        if let Some(x) = update.tasks {
+            self.tasks = x;
+        }
+        if let Some(x) = update.tags {
+            self.tags = x;
+        }
+        self.updated_at = Some(Local::now());
+        self.incomplete = update.failed;
+    }
+
+    /// Forces a refresh on the next check, e.g. after resuming from suspend.
+    pub fn invalidate(&mut self) {
+        self.updated_at = None;
+    }
+
+    pub fn is_stale(&self, refresh_min: u32) -> bool {
+        let refresh_min = if self.incomplete {
+            refresh_min.min(RETRY_MIN)
+        } else {
+            refresh_min
+        };
+        match self.updated_at {
+            Some(t) => Local::now() - t >= Duration::minutes(refresh_min as i64),
+            None => true,
+        }
+    }
+
+    pub fn project(&self, id: Option<u64>) -> Option<&Project> {
+        id.and_then(|x| self.projects.get(&x))
+    }
+
+    pub fn task(&self, id: Option<u64>) -> Option<&Task> {
+        id.and_then(|x| self.tasks.get(&x))
+    }
+
+    pub fn client(&self, project: &Project) -> Option<&Client> {
+        project.client_id.and_then(|x| self.clients.get(&x))
+    }
+
+    pub fn project_name(&self, entry: &TimeEntry) -> Option<String> {
+        self.project(entry.project_id)
+            .map(|x| x.name.clone())
+            .or_else(|| entry.project_name.clone())
+    }
+
+    pub fn client_name(&self, entry: &TimeEntry) -> Option<String> {
+        self.project(entry.project_id)
+            .and_then(|x| self.client(x))
+            .map(|x| x.name.clone())
+            .or_else(|| entry.client_name.clone())
+    }
+
+    pub fn project_color(&self, entry: &TimeEntry) -> Option<String> {
+        self.project(entry.project_id)
+            .and_then(|x| x.color.clone())
+            .or_else(|| entry.project_color.clone())
+    }
+
+    /// Looks up an active project by name, ignoring case.
+    pub fn find_project(&self, name: &str) -> Option<&Project> {
+        let name = name.to_lowercase();
+        self.projects
+            .values()
+            .filter(|x| x.active)
+            .find(|x| x.name.to_lowercase() == name)
+    }
+
+    /// Returns the canonical spelling of a tag in the given workspace, or
+    /// the name as given if the tag does not exist yet.
+    pub fn tag_name(&self, workspace_id: u64, name: &str) -> String {
+        let lower = name.to_lowercase();
+        self.tags
+            .values()
+            .find(|x| x.workspace_id == workspace_id && x.name.to_lowercase() == lower)
+            .map(|x| x.name.clone())
+            .unwrap_or_else(|| name.to_string())
+    }
+}
+
+lazy_static! {
+    pub static ref CATALOG: RwLock<Catalog> = RwLock::new(Default::default());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failed_parts_keep_the_cache_and_back_off() {
+        let mut catalog = Catalog::default();
+        assert!(catalog.is_stale(60));
+
+        catalog.apply(CatalogUpdate {
+            default_workspace_id: Some(1),
+            tasks: None,
+            failed: true,
+            ..Default::default()
+        });
+        assert!(!catalog.is_stale(60));
+        assert!(catalog.incomplete);
+
+        catalog.apply(CatalogUpdate {
+            failed: true,
+            ..Default::default()
+        });
+        assert_eq!(catalog.default_workspace_id, Some(1));
+
+        catalog.apply(CatalogUpdate::default());
+        assert!(!catalog.incomplete);
+    }
+}
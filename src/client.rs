@@ -0,0 +1,25 @@
+use std::io::{Read, Write};
+use std::net::Shutdown;
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::time::Duration;
+
+use failure::{format_err, Error};
+
+/// How long to wait for the daemon to reply.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Sends one command to the daemon socket and returns its reply.
+pub fn request(socket: &Path, command: &str) -> Result<String, Error> {
+    let mut stream = UnixStream::connect(socket)
+        .map_err(|e| format_err!("cannot connect to {}: {}", socket.display(), e))?;
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    writeln!(stream, "{}", command)?;
+    stream.shutdown(Shutdown::Write)?;
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply)?;
+    match reply.strip_prefix("error: ") {
+        Some(e) => Err(format_err!("{}", e.trim())),
+        None => Ok(reply.trim().to_string()),
+    }
+}
@@ -1,24 +1,68 @@
+use std::collections::HashMap;
+use std::env;
 use std::fs::File;
 use std::io::{BufReader, Read};
+use std::path::PathBuf;
 use std::sync::RwLock;
 
 use lazy_static::lazy_static;
-use serde_derive::Deserialize;
+use regex::Regex;
+use serde::{Deserialize as _, Deserializer, Serializer};
+use serde_derive::{Deserialize, Serialize};
 
-use failure::Error;
+use failure::{format_err, Error};
 
-#[derive(Debug, Default, Deserialize)]
+use crate::duration;
+
+#[derive(Debug, Default, Deserialize, Serialize)]
 pub struct Config {
     pub version: u8,
+    #[serde(default)]
     pub toggl_token: String,
     pub socket: Option<String>,
 
+    #[serde(default = "default_catalog_refresh_min")]
+    pub catalog_refresh_min: u32,
+
+    #[serde(default)]
+    pub tracker: TrackerConfig,
+
     #[serde(default)]
     pub notification: NotificationConfig,
 
     #[serde(default)]
     pub pomodoro: PomodoroConfig,
 
+    #[serde(default)]
+    pub budget: BudgetConfig,
+
+    #[serde(default)]
+    pub history: HistoryConfig,
+
+    #[serde(default)]
+    pub goals: GoalsConfig,
+
+    #[serde(default)]
+    pub idle: IdleConfig,
+
+    #[serde(default)]
+    pub plan: PlanConfig,
+
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+
+    #[serde(default)]
+    pub api: ApiConfig,
+
+    #[serde(default)]
+    pub team: TeamConfig,
+
+    #[serde(default)]
+    pub sync: SyncConfig,
+
+    #[serde(default)]
+    pub log: LogConfig,
+
     #[serde(default)]
     pub format: FormatConfig,
 }
@@ -31,14 +75,110 @@ impl Config {
         let mut buf_reader = BufReader::new(file);
         let mut contents = String::new();
         buf_reader.read_to_string(&mut contents)?;
-        let config = toml::from_str(&contents)?;
+        let config: Config = toml::from_str(&contents)?;
+        config.validate()?;
         *c = config;
 
         Ok(())
     }
+
+    /// Rejects settings that would only fail later, at runtime.
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.pomodoro.day_start_hour > 23 {
+            return Err(format_err!(
+                "pomodoro.day_start_hour must be 0-23, got {}",
+                self.pomodoro.day_start_hour
+            ));
+        }
+        for (project, budget) in &self.budget.projects {
+            if duration::parse(budget).is_none() {
+                return Err(format_err!(
+                    "invalid budget for {} in budget.projects: {}",
+                    project,
+                    budget
+                ));
+            }
+        }
+        if let TrackerConfig::Toggl = self.tracker {
+            if self.toggl_token.trim().is_empty() {
+                return Err(format_err!(
+                    "toggl_token must be set when the tracker is Toggl"
+                ));
+            }
+        }
+        if self.api.listen.is_some()
+            && self
+                .api
+                .token
+                .as_deref()
+                .is_none_or(|x| x.trim().is_empty())
+        {
+            return Err(format_err!(
+                "api.token must be set to a non-empty token with api.listen"
+            ));
+        }
+        if self.goals.is_enabled() && !self.history.enabled {
+            return Err(format_err!(
+                "goals are counted from the history; set history.enabled = true"
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Compiles a regex option at load time, so invalid patterns fail there.
+fn deserialize_regex<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Regex>, D::Error> {
+    Option::<String>::deserialize(d)?
+        .map(|x| Regex::new(&x).map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+fn serialize_regex<S: Serializer>(regex: &Option<Regex>, s: S) -> Result<S::Ok, S::Error> {
+    match regex {
+        Some(regex) => s.serialize_some(regex.as_str()),
+        None => s.serialize_none(),
+    }
+}
+
+fn default_catalog_refresh_min() -> u32 {
+    60
+}
+
+/// Returns `$XDG_DATA_HOME/toggdoro`.
+pub fn data_dir() -> PathBuf {
+    let data_home = env::var("XDG_DATA_HOME")
+        .unwrap_or_else(|_| env::var("HOME").unwrap_or_else(|_| ".".to_string()) + "/.local/share");
+    PathBuf::from(data_home).join("toggdoro")
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(tag = "backend", rename_all = "lowercase")]
+pub enum TrackerConfig {
+    #[default]
+    Toggl,
+    Local {
+        path: Option<String>,
+    },
+    Timewarrior {
+        command: Option<String>,
+        project_prefix: Option<String>,
+    },
+    Clockify {
+        url: Option<String>,
+        token: String,
+        workspace_id: Option<String>,
+    },
+    Kimai {
+        url: String,
+        token: String,
+        /// Project of entries started without one.
+        project_id: Option<u64>,
+        /// Activity of entries started by toggdoro; Kimai requires one.
+        activity_id: Option<u64>,
+    },
 }
 
-#[derive(Debug, Default, Deserialize)]
+#[derive(Debug, Default, Deserialize, Serialize)]
 pub struct NotificationConfig {
     #[serde(default)]
     pub dbus: bool,
@@ -48,7 +188,7 @@ pub struct NotificationConfig {
     pub slack: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct PomodoroConfig {
     #[serde(default = "default_pomodoro_min")]
     pub pomodoro_min: u32,
@@ -61,6 +201,83 @@ pub struct PomodoroConfig {
 
     #[serde(default = "default_long_break_after")]
     pub long_break_after: u32,
+
+    #[serde(default, rename = "profile")]
+    pub profiles: Vec<PomodoroProfile>,
+
+    #[serde(default = "default_break_match")]
+    pub break_match: MatchConfig,
+
+    #[serde(default)]
+    pub ignore: MatchConfig,
+
+    /// Largest gap in seconds between two entries of the same set.
+    #[serde(default = "default_max_gap_sec")]
+    pub max_gap_sec: u32,
+
+    /// Gaps shorter than this count as breaks instead of ending the set.
+    pub reset_idle_min: Option<u32>,
+
+    /// Breaks at least this long end the set; defaults to `long_break_min`.
+    pub reset_break_min: Option<u32>,
+
+    #[serde(default)]
+    pub reset_on_new_day: bool,
+
+    #[serde(default)]
+    pub day_start_hour: u32,
+}
+
+/// Matches entries whose description matches the regex, or that have any of
+/// the given tags or projects.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct MatchConfig {
+    #[serde(
+        default,
+        deserialize_with = "deserialize_regex",
+        serialize_with = "serialize_regex"
+    )]
+    pub description: Option<Regex>,
+
+    #[serde(default)]
+    pub tags: Vec<String>,
+
+    #[serde(default)]
+    pub projects: Vec<String>,
+
+    #[serde(default)]
+    pub project_ids: Vec<u64>,
+}
+
+/// Overrides the pomodoro lengths for entries matching any of the given
+/// projects, clients, tags or description regex, or tagged `profile:<name>`.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct PomodoroProfile {
+    pub name: String,
+
+    pub pomodoro_min: Option<u32>,
+
+    pub short_break_min: Option<u32>,
+
+    pub long_break_min: Option<u32>,
+
+    pub long_break_after: Option<u32>,
+
+    #[serde(default)]
+    pub projects: Vec<String>,
+
+    #[serde(default)]
+    pub clients: Vec<String>,
+
+    #[serde(default)]
+    pub tags: Vec<String>,
+
+    #[serde(
+        default,
+        deserialize_with = "deserialize_regex",
+        serialize_with = "serialize_regex"
+    )]
+    pub description: Option<Regex>,
 }
 
 fn default_pomodoro_min() -> u32 {
@@ -75,6 +292,16 @@ fn default_long_break_min() -> u32 {
 fn default_long_break_after() -> u32 {
     4
 }
+fn default_max_gap_sec() -> u32 {
+    120
+}
+fn default_break_match() -> MatchConfig {
+    MatchConfig {
+        description: Some(Regex::new("^Pomodoro Break$").unwrap()),
+        tags: vec!["pomodoro-break".to_string()],
+        ..Default::default()
+    }
+}
 
 impl Default for PomodoroConfig {
     fn default() -> Self {
@@ -83,11 +310,243 @@ impl Default for PomodoroConfig {
             short_break_min: default_short_break_min(),
             long_break_min: default_long_break_min(),
             long_break_after: default_long_break_after(),
+            profiles: Vec::new(),
+            break_match: default_break_match(),
+            ignore: Default::default(),
+            max_gap_sec: default_max_gap_sec(),
+            reset_idle_min: None,
+            reset_break_min: None,
+            reset_on_new_day: false,
+            day_start_hour: 0,
         }
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct BudgetConfig {
+    /// Prefix of budget tags, e.g. `budget:` for `budget:45m`.
+    #[serde(default)]
+    pub tag_prefix: String,
+
+    /// Budgets keyed by project name, e.g. `"Support" = "1h30m"`.
+    #[serde(default)]
+    pub projects: HashMap<String, String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct HistoryConfig {
+    #[serde(default = "default_history_enabled")]
+    pub enabled: bool,
+
+    /// Defaults to `$XDG_DATA_HOME/toggdoro/history.jsonl`.
+    pub path: Option<String>,
+}
+
+fn default_history_enabled() -> bool {
+    true
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_history_enabled(),
+            path: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogOutput {
+    /// journald when started by systemd, the log file otherwise.
+    #[default]
+    Auto,
+    Journald,
+    File,
+    Stderr,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct LogConfig {
+    /// One of `off`, `error`, `warn`, `info`, `debug` and `trace`.
+    #[serde(default = "default_log_level")]
+    pub level: String,
+
+    /// Levels keyed by module path, e.g. `"toggdoro::toggl" = "debug"`.
+    /// The longest matching path wins.
+    #[serde(default)]
+    pub modules: HashMap<String, String>,
+
+    #[serde(default)]
+    pub output: LogOutput,
+
+    /// Log file; defaults to `$XDG_DATA_HOME/toggdoro/toggdoro.log`.
+    pub path: Option<String>,
+}
+
+fn default_log_level() -> String {
+    "info".to_string()
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            level: default_log_level(),
+            modules: HashMap::new(),
+            output: Default::default(),
+            path: None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct SyncConfig {
+    /// HTTP API of the leader instance to follow, e.g.
+    /// `http://10.0.0.5:8491`.  Following is disabled unless set.
+    pub leader: Option<String>,
+
+    /// API token of the leader.
+    pub token: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Privacy {
+    /// Shares the mode, remaining time, description and project.
+    #[default]
+    Full,
+    /// Shares the mode and remaining time only.
+    Mode,
+    /// Shares whether the member is in a pomodoro only.
+    Busy,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TeamMember {
+    pub name: String,
+    /// The member's Toggl API token.
+    pub token: String,
+    #[serde(default)]
+    pub privacy: Privacy,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TeamConfig {
+    /// The team board is disabled unless members are given.
+    #[serde(default, rename = "member")]
+    pub members: Vec<TeamMember>,
+
+    #[serde(default = "default_team_refresh_sec")]
+    pub refresh_sec: u32,
+
+    /// Slack webhook the board is posted to when someone's mode changes.
+    pub slack: Option<String>,
+}
+
+fn default_team_refresh_sec() -> u32 {
+    60
+}
+
+impl Default for TeamConfig {
+    fn default() -> Self {
+        Self {
+            members: Vec::new(),
+            refresh_sec: default_team_refresh_sec(),
+            slack: None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct ApiConfig {
+    /// Address to serve the HTTP API and dashboard on, e.g. `0.0.0.0:8491`.
+    /// Open the dashboard as `/?access_token=<token>`.  The API is
+    /// disabled unless set.
+    pub listen: Option<String>,
+
+    /// Bearer token clients must send; required when `listen` is set.
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct MetricsConfig {
+    /// Address to serve Prometheus metrics on, e.g. `127.0.0.1:9184`.  The
+    /// endpoint is disabled unless set.
+    pub listen: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct PlanConfig {
+    /// Directory of the daily plan files, named `YYYY-MM-DD.txt`.  Defaults
+    /// to `$XDG_DATA_HOME/toggdoro/plans`.
+    pub dir: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct GoalsConfig {
+    pub daily_pomodoros: Option<u32>,
+
+    /// Weekly pomodoro goals keyed by project name.
+    #[serde(default)]
+    pub weekly: HashMap<String, u32>,
+}
+
+impl GoalsConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.daily_pomodoros.is_some() || !self.weekly.is_empty()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IdleSource {
+    Logind,
+    Screensaver,
+    Command,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IdleAction {
+    #[default]
+    Notify,
+    /// Stops the running entry backdated to when the user went idle.
+    Stop,
+    /// Marks the running pomodoro as interrupted.
+    Interrupt,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct IdleConfig {
+    /// Idle detection is disabled unless a source is given.
+    pub source: Option<IdleSource>,
+
+    /// Command printing the idle time in milliseconds, e.g. `xprintidle`.
+    pub command: Option<String>,
+
+    #[serde(default = "default_idle_min")]
+    pub idle_min: u32,
+
+    #[serde(default)]
+    pub action: IdleAction,
+}
+
+fn default_idle_min() -> u32 {
+    10
+}
+
+impl Default for IdleConfig {
+    fn default() -> Self {
+        Self {
+            source: None,
+            command: None,
+            idle_min: default_idle_min(),
+            action: Default::default(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
 pub struct FormatConfig {
     #[serde(default = "default_format_idle")]
     pub idle: String,
@@ -152,3 +611,88 @@ fn default_format_task() -> String {
 lazy_static! {
     pub static ref CONFIG: RwLock<Config> = RwLock::new(Default::default());
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn regexes_are_checked_at_load() {
+        let config: Config =
+            toml::from_str("version = 1\n[pomodoro.ignore]\ndescription = \"^lunch\"\n").unwrap();
+        assert!(config
+            .pomodoro
+            .ignore
+            .description
+            .unwrap()
+            .is_match("lunch break"));
+
+        let bad = "version = 1\n[[pomodoro.profile]]\nname = \"x\"\ndescription = \"(\"\n";
+        assert!(toml::from_str::<Config>(bad).is_err());
+    }
+
+    #[test]
+    fn day_start_hour_is_validated() {
+        let config: Config = toml::from_str(
+            "version = 1
+[pomodoro]
+day_start_hour = 24
+",
+        )
+        .unwrap();
+        assert!(config.validate().is_err());
+        let config: Config = toml::from_str(
+            "version = 1
+toggl_token = \"t\"
+[pomodoro]
+day_start_hour = 4
+",
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn project_budgets_are_validated() {
+        let budget = |value: &str| {
+            toml::from_str::<Config>(&format!(
+                "version = 1\ntoggl_token = \"t\"\n[budget.projects]\n\"Support\" = \"{}\"\n",
+                value
+            ))
+            .unwrap()
+            .validate()
+        };
+        assert!(budget("1h30m").is_ok());
+        let e = budget("90 minutes").unwrap_err();
+        assert!(e.to_string().contains("Support"));
+    }
+
+    #[test]
+    fn api_token_must_not_be_empty() {
+        let api = |token: &str| {
+            toml::from_str::<Config>(&format!(
+                "version = 1\ntoggl_token = \"t\"\n[api]\nlisten = \"127.0.0.1:8491\"\n{}",
+                token
+            ))
+            .unwrap()
+            .validate()
+        };
+        assert!(api("").is_err());
+        assert!(api("token = \"\"").is_err());
+        assert!(api("token = \"  \"").is_err());
+        assert!(api("token = \"s3cret\"").is_ok());
+    }
+
+    #[test]
+    fn toggl_token_must_not_be_empty() {
+        let toggl = |extra: &str| {
+            toml::from_str::<Config>(&format!("version = 1\n{}", extra))
+                .unwrap()
+                .validate()
+        };
+        assert!(toggl("").is_err());
+        assert!(toggl("toggl_token = \" \"").is_err());
+        assert!(toggl("toggl_token = \"t\"").is_ok());
+        assert!(toggl("[tracker]\nbackend = \"local\"").is_ok());
+    }
+}
@@ -0,0 +1,154 @@
+use std::fmt::Write;
+
+use failure::Error;
+
+use crate::config::Config;
+
+/// Returns the keys missing from the config file, which therefore take
+/// their default value, as dotted paths.  A table missing as a whole is
+/// listed once.  Keys without a value, such as unset options, are left
+/// out.
+pub fn defaulted_keys(file: &toml::Value, config: &Config) -> Result<Vec<String>, Error> {
+    let effective = toml::Value::try_from(config)?;
+    let mut keys = Vec::new();
+    add_missing(file, &effective, "", &mut keys);
+    Ok(keys)
+}
+
+fn add_missing(file: &toml::Value, effective: &toml::Value, prefix: &str, keys: &mut Vec<String>) {
+    let table = match effective.as_table() {
+        Some(table) => table,
+        None => return,
+    };
+    for (name, value) in table {
+        let key = if prefix.is_empty() {
+            name.clone()
+        } else {
+            format!("{}.{}", prefix, name)
+        };
+        match file.get(name) {
+            Some(x) => add_missing(x, value, &key, keys),
+            None => keys.push(key),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Verdict {
+    Pass,
+    /// Worth a look, but not a failure.
+    Warn,
+    Fail,
+}
+
+#[derive(Debug)]
+pub struct Check {
+    pub verdict: Verdict,
+    pub name: String,
+    pub detail: String,
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+    pub checks: Vec<Check>,
+}
+
+impl Report {
+    pub fn add(&mut self, verdict: Verdict, name: &str, detail: &str) {
+        self.checks.push(Check {
+            verdict,
+            name: name.to_string(),
+            detail: detail.to_string(),
+        });
+    }
+
+    /// Records the outcome of a check returning a detail on success.
+    pub fn check(&mut self, name: &str, result: Result<String, Error>) {
+        match result {
+            Ok(detail) => self.add(Verdict::Pass, name, &detail),
+            Err(e) => self.add(Verdict::Fail, name, &e.to_string()),
+        }
+    }
+
+    pub fn failures(&self) -> usize {
+        self.checks
+            .iter()
+            .filter(|x| x.verdict == Verdict::Fail)
+            .count()
+    }
+
+    /// Exit code of the `doctor` command: 0 if every check passed, 1
+    /// otherwise.
+    pub fn exit_code(&self) -> i32 {
+        if self.failures() == 0 {
+            0
+        } else {
+            1
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let mut s = String::new();
+        for check in &self.checks {
+            let label = match check.verdict {
+                Verdict::Pass => "ok",
+                Verdict::Warn => "warn",
+                Verdict::Fail => "FAIL",
+            };
+            let _ = write!(s, "[{:>4}] {}", label, check.name);
+            if !check.detail.is_empty() {
+                let _ = write!(s, ": {}", check.detail);
+            }
+            s.push('\n');
+        }
+        let _ = writeln!(
+            s,
+            "{} checks, {} failed",
+            self.checks.len(),
+            self.failures()
+        );
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaulted_keys_follow_the_config() {
+        let source = "version = 1
+toggl_token = \"t\"
+[pomodoro]
+pomodoro_min = 50
+[log]
+level = \"debug\"
+";
+        let file: toml::Value = toml::from_str(source).unwrap();
+        let config: Config = toml::from_str(source).unwrap();
+        let keys = defaulted_keys(&file, &config).unwrap();
+
+        for key in &[
+            "catalog_refresh_min",
+            "pomodoro.short_break_min",
+            "pomodoro.ignore",
+            "pomodoro.break_match",
+            "log.modules",
+            "log.output",
+            "history",
+            "format",
+        ] {
+            assert!(keys.iter().any(|x| x == key), "{} not in {:?}", key, keys);
+        }
+        for key in &[
+            "version",
+            "toggl_token",
+            "pomodoro.pomodoro_min",
+            "log.level",
+            "socket",
+            "log.path",
+        ] {
+            assert!(!keys.iter().any(|x| x == key), "{} in {:?}", key, keys);
+        }
+    }
+}
@@ -0,0 +1,23 @@
+use chrono::Duration;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    static ref DURATION_RE: Regex = Regex::new(r"^(?:(\d+)h)?(?:(\d+)m(?:in)?)?$").unwrap();
+}
+
+/// Parses durations such as `2h`, `90m`, `1h30m` or `25min`.
+pub fn parse(s: &str) -> Option<Duration> {
+    let cap = DURATION_RE.captures(s.trim())?;
+    if cap.get(1).is_none() && cap.get(2).is_none() {
+        return None;
+    }
+    let hours: i64 = cap.get(1).map_or(Some(0), |x| x.as_str().parse().ok())?;
+    let mins: i64 = cap.get(2).map_or(Some(0), |x| x.as_str().parse().ok())?;
+    Some(Duration::minutes(hours * 60 + mins))
+}
+
+/// Formats seconds as `H:MM`.
+pub fn hm(secs: i64) -> String {
+    format!("{}:{:02}", secs / 3600, secs / 60 % 60)
+}
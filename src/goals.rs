@@ -0,0 +1,199 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate};
+use failure::{format_err, Error};
+use serde_derive::Serialize;
+
+use crate::config::{GoalsConfig, HistoryConfig};
+use crate::history::{self, Record};
+use crate::pomodoro;
+
+/// Streaks are counted back at most this many days.
+const MAX_STREAK_DAYS: i64 = 366;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Progress {
+    pub day: NaiveDate,
+    pub today: u32,
+    pub week: u32,
+    pub streak: u32,
+    /// Pomodoros this week and the weekly goal, keyed by project.
+    pub projects: BTreeMap<String, (u32, u32)>,
+}
+
+/// Pomodoros per day and per day and project, loaded from the local
+/// history once and kept up to date as phases finish.
+#[derive(Debug, Default)]
+pub struct Tally {
+    day_start_hour: u32,
+    days: BTreeMap<NaiveDate, u32>,
+    projects: BTreeMap<(NaiveDate, String), u32>,
+}
+
+impl Tally {
+    pub fn load(
+        history_config: &HistoryConfig,
+        day_start_hour: u32,
+        now: DateTime<Local>,
+    ) -> Result<Self, Error> {
+        let first = pomodoro::day_of(&now, day_start_hour) - Duration::days(MAX_STREAK_DAYS);
+        let start = pomodoro::start_of_day(first, day_start_hour)
+            .ok_or_else(|| format_err!("invalid date: {}", first))?;
+        let mut tally = Tally {
+            day_start_hour,
+            ..Default::default()
+        };
+        for record in history::load(history_config, start, now + Duration::days(1))? {
+            tally.add(&record);
+        }
+        Ok(tally)
+    }
+
+    /// Counts the record if it is a pomodoro.
+    pub fn add(&mut self, record: &Record) {
+        if !record.is_pomodoro() {
+            return;
+        }
+        let day = pomodoro::day_of(&record.start, self.day_start_hour);
+        *self.days.entry(day).or_default() += 1;
+        if let Some(ref project) = record.project {
+            *self.projects.entry((day, project.clone())).or_default() += 1;
+        }
+    }
+
+    /// Computes today's and this week's progress.
+    pub fn progress(&self, config: &GoalsConfig, now: DateTime<Local>) -> Progress {
+        let today = pomodoro::day_of(&now, self.day_start_hour);
+        let monday = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+        let first = today - Duration::days(MAX_STREAK_DAYS);
+        let count = |day: &NaiveDate| self.days.get(day).cloned().unwrap_or(0);
+
+        let week = self.days.range(monday..).map(|x| x.1).sum();
+        let projects = config
+            .weekly
+            .iter()
+            .map(|(project, goal)| {
+                let done = self
+                    .projects
+                    .iter()
+                    .filter(|((day, p), _)| *day >= monday && p == project)
+                    .map(|x| x.1)
+                    .sum();
+                (project.clone(), (done, *goal))
+            })
+            .collect();
+
+        let done = |day: &NaiveDate| {
+            config
+                .daily_pomodoros
+                .is_some_and(|goal| count(day) >= goal)
+        };
+        let mut day = if done(&today) {
+            today
+        } else {
+            today - Duration::days(1)
+        };
+        let mut streak = 0;
+        while day > first && done(&day) {
+            streak += 1;
+            day -= Duration::days(1);
+        }
+
+        Progress {
+            day: today,
+            today: count(&today),
+            week,
+            streak,
+            projects,
+        }
+    }
+}
+
+/// Returns messages for the goals reached between `before` and `after`.
+pub fn reached(config: &GoalsConfig, before: &Progress, after: &Progress) -> Vec<String> {
+    let mut messages = Vec::new();
+    if before.day != after.day {
+        return messages;
+    }
+    if let Some(goal) = config.daily_pomodoros {
+        if before.today < goal && after.today >= goal {
+            messages.push(format!(
+                "Daily goal reached: {} pomodoros, {} day streak",
+                after.today, after.streak
+            ));
+        }
+    }
+    for (project, &(done, goal)) in &after.projects {
+        let prev = before.projects.get(project).map_or(0, |x| x.0);
+        if prev < goal && done >= goal {
+            messages.push(format!(
+                "Weekly goal for {} reached: {} pomodoros",
+                project, done
+            ));
+        }
+    }
+    messages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::Outcome;
+    use crate::pomodoro::PomodoroMode;
+    use chrono::TimeZone;
+    use std::collections::HashMap;
+
+    fn pomodoro(start: DateTime<Local>, project: &str) -> Record {
+        Record {
+            entry_id: 1,
+            entry_ids: vec![1],
+            mode: PomodoroMode::Work,
+            outcome: Outcome::Completed,
+            description: "write".to_string(),
+            project: Some(project.to_string()),
+            tags: Vec::new(),
+            start,
+            end: start + Duration::minutes(25),
+            planned_secs: 25 * 60,
+            actual_secs: 25 * 60,
+            count: 1,
+            long_break: false,
+            notifications: 0,
+            interrupted: false,
+            interruptions: Vec::new(),
+            voided: false,
+        }
+    }
+
+    #[test]
+    fn tally_keeps_running_totals() {
+        // Thursday; the week starts on Monday the 29th.
+        let at = |d, h| Local.with_ymd_and_hms(2024, 5, d, h, 0, 0).unwrap();
+        let config = GoalsConfig {
+            daily_pomodoros: Some(2),
+            weekly: HashMap::from([("docs".to_string(), 3)]),
+        };
+        let mut tally = Tally::default();
+        for record in [
+            pomodoro(at(1, 9), "docs"),
+            pomodoro(at(1, 10), "code"),
+            pomodoro(at(2, 9), "docs"),
+        ] {
+            tally.add(&record);
+        }
+        let before = tally.progress(&config, at(2, 12));
+        assert_eq!((before.today, before.week, before.streak), (1, 3, 1));
+        assert_eq!(before.projects["docs"], (2, 3));
+
+        tally.add(&pomodoro(at(2, 11), "docs"));
+        let after = tally.progress(&config, at(2, 12));
+        assert_eq!((after.today, after.streak), (2, 2));
+        assert_eq!(
+            reached(&config, &before, &after),
+            vec![
+                "Daily goal reached: 2 pomodoros, 2 day streak",
+                "Weekly goal for docs reached: 3 pomodoros"
+            ]
+        );
+    }
+}
@@ -0,0 +1,222 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+
+use chrono::{DateTime, Local};
+use failure::Error;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::config::{self, HistoryConfig};
+use crate::pomodoro::PomodoroMode;
+
+/// Phases running longer than planned by more than this are overruns.  It
+/// matches the delay of the first reminder.
+const OVERRUN_GRACE_SECS: i64 = 300;
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Completed,
+    /// Stopped before its planned end.
+    #[serde(alias = "abandoned")]
+    Stopped,
+    Overrun,
+    /// The user went idle during the phase.
+    Interrupted,
+    /// Given up with the `abandon` command, so it never counts.
+    Voided,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InterruptionKind {
+    Internal,
+    External,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Interruption {
+    pub kind: InterruptionKind,
+    pub at: DateTime<Local>,
+    pub note: Option<String>,
+}
+
+/// A finished work or break phase.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Record {
+    /// The latest entry of the phase.
+    pub entry_id: u64,
+    /// All entries of the phase, which spans several when continued.
+    #[serde(default)]
+    pub entry_ids: Vec<u64>,
+    pub mode: PomodoroMode,
+    pub outcome: Outcome,
+    pub description: String,
+    pub project: Option<String>,
+    pub tags: Vec<String>,
+    pub start: DateTime<Local>,
+    pub end: DateTime<Local>,
+    pub planned_secs: i64,
+    pub actual_secs: i64,
+    /// Position of the pomodoro in its set.
+    pub count: u32,
+    pub long_break: bool,
+    pub notifications: u32,
+    #[serde(default)]
+    pub interrupted: bool,
+    #[serde(default)]
+    pub interruptions: Vec<Interruption>,
+    #[serde(default)]
+    pub voided: bool,
+}
+
+impl Record {
+    /// Closes the phase at `end` and decides its outcome.
+    pub fn finish(&mut self, end: DateTime<Local>) {
+        self.end = end;
+        self.actual_secs = (end - self.start).num_seconds();
+        self.outcome = if self.voided {
+            Outcome::Voided
+        } else if self.interrupted {
+            Outcome::Interrupted
+        } else if self.actual_secs < self.planned_secs {
+            Outcome::Stopped
+        } else if self.actual_secs > self.planned_secs + OVERRUN_GRACE_SECS {
+            Outcome::Overrun
+        } else {
+            Outcome::Completed
+        };
+    }
+}
+
+impl Record {
+    /// Returns true for work phases that count as a pomodoro.
+    pub fn is_pomodoro(&self) -> bool {
+        self.mode == PomodoroMode::Work
+            && (self.outcome == Outcome::Completed || self.outcome == Outcome::Overrun)
+    }
+
+    pub fn count_interruptions(&self, kind: InterruptionKind) -> u32 {
+        self.interruptions.iter().filter(|x| x.kind == kind).count() as u32
+    }
+
+    /// Returns the ids of all entries of the phase, including the latest
+    /// one for records saved before `entry_ids` existed.
+    pub fn all_entry_ids(&self) -> impl Iterator<Item = u64> + '_ {
+        self.entry_ids
+            .iter()
+            .cloned()
+            .chain(Some(self.entry_id).filter(|x| !self.entry_ids.contains(x)))
+    }
+}
+
+/// The running phase is kept here so a restart keeps its interruptions.
+fn running_path() -> PathBuf {
+    config::data_dir().join("phase.json")
+}
+
+/// Loads the phase running when toggdoro last saved it, if any.
+pub fn load_running() -> Result<Option<Record>, Error> {
+    match File::open(running_path()) {
+        Ok(file) => Ok(Some(serde_json::from_reader(BufReader::new(file))?)),
+        Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Saves the running phase, or removes the saved one when there is none.
+pub fn save_running(record: Option<&Record>) -> Result<(), Error> {
+    let path = running_path();
+    match record {
+        Some(record) => {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            fs::write(path, serde_json::to_string(record)?)?;
+        }
+        None => match fs::remove_file(path) {
+            Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => (),
+            result => result?,
+        },
+    }
+    Ok(())
+}
+
+pub fn path(config: &HistoryConfig) -> PathBuf {
+    if let Some(ref path) = config.path {
+        return PathBuf::from(path);
+    }
+    config::data_dir().join("history.jsonl")
+}
+
+pub fn append(config: &HistoryConfig, record: &Record) -> Result<(), Error> {
+    if !config.enabled {
+        return Ok(());
+    }
+    let path = path(config);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", serde_json::to_string(record)?)?;
+    Ok(())
+}
+
+/// Loads the records of phases started in `[start, end)`, oldest first.
+pub fn load(
+    config: &HistoryConfig,
+    start: DateTime<Local>,
+    end: DateTime<Local>,
+) -> Result<Vec<Record>, Error> {
+    let file = match File::open(path(config)) {
+        Ok(file) => file,
+        Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut records = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: Record = serde_json::from_str(&line)?;
+        if record.start >= start && record.start < end {
+            records.push(record);
+        }
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_old_records() {
+        let record: Record = serde_json::from_str(
+            r#"{"entry_id":7,"mode":"Work","outcome":"abandoned","description":"write",
+                "project":null,"tags":[],"start":"2024-05-24T09:00:00+02:00",
+                "end":"2024-05-24T09:10:00+02:00","planned_secs":1500,"actual_secs":600,
+                "count":1,"long_break":false,"notifications":0}"#,
+        )
+        .unwrap();
+        assert_eq!(record.outcome, Outcome::Stopped);
+        assert_eq!(record.all_entry_ids().collect::<Vec<_>>(), vec![7]);
+    }
+
+    #[test]
+    fn all_entry_ids_covers_continued_phases() {
+        let mut record: Record = serde_json::from_str(
+            r#"{"entry_id":9,"entry_ids":[7,9],"mode":"Work","outcome":"completed",
+                "description":"write","project":null,"tags":[],
+                "start":"2024-05-24T09:00:00+02:00","end":"2024-05-24T09:25:00+02:00",
+                "planned_secs":1500,"actual_secs":0,"count":1,"long_break":false,
+                "notifications":0}"#,
+        )
+        .unwrap();
+        assert_eq!(record.all_entry_ids().collect::<Vec<_>>(), vec![7, 9]);
+        record.voided = true;
+        record.finish(record.end);
+        assert_eq!(record.outcome, Outcome::Voided);
+    }
+}
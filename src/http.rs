@@ -0,0 +1,107 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+use failure::{format_err, Error};
+
+/// Requests are small, so a client that stalls longer than this is dropped.
+const READ_TIMEOUT_SECS: u64 = 5;
+
+/// Bodies only carry command arguments, so anything larger is refused.
+const MAX_BODY_LEN: usize = 4096;
+
+/// The parts of an HTTP/1.x request toggdoro cares about.
+#[derive(Debug)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Request {
+    /// Returns the first header with the given name, compared
+    /// case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|x| x.0.eq_ignore_ascii_case(name))
+            .map(|x| x.1.as_str())
+    }
+
+    /// Splits the path from the query string.
+    pub fn split_path(&self) -> (&str, Option<&str>) {
+        match self.path.find('?') {
+            Some(i) => (&self.path[..i], Some(&self.path[i + 1..])),
+            None => (&self.path, None),
+        }
+    }
+}
+
+/// Reads the request line, headers and a body of `Content-Length` bytes.
+pub fn read_request(stream: &TcpStream) -> Result<Request, Error> {
+    stream.set_read_timeout(Some(Duration::from_secs(READ_TIMEOUT_SECS)))?;
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let (method, path) = match (parts.next(), parts.next()) {
+        (Some(method), Some(path)) => (method.to_string(), path.to_string()),
+        _ => return Err(format_err!("invalid request line: {}", line.trim())),
+    };
+
+    let mut headers = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(i) = header.find(':') {
+            headers.push((
+                header[..i].trim().to_string(),
+                header[i + 1..].trim().to_string(),
+            ));
+        }
+    }
+    let mut request = Request {
+        method,
+        path,
+        headers,
+        body: String::new(),
+    };
+    let len: usize = match request.header("Content-Length") {
+        Some(len) => len
+            .parse()
+            .map_err(|_| format_err!("invalid Content-Length: {}", len))?,
+        None => 0,
+    };
+    if len > MAX_BODY_LEN {
+        return Err(format_err!("request body too large: {}", len));
+    }
+    let mut body = vec![0; len];
+    reader.read_exact(&mut body)?;
+    request.body = String::from_utf8(body)?;
+    Ok(request)
+}
+
+/// Writes a complete response and lets the connection close.
+pub fn respond(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &[u8],
+) -> Result<(), Error> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )?;
+    stream.write_all(body)?;
+    Ok(())
+}
@@ -0,0 +1,183 @@
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chrono::{DateTime, Duration, Local};
+use dbus::stdintf::org_freedesktop_dbus::Properties;
+use dbus::{BusType, Connection, Message};
+use failure::{format_err, Error};
+
+use crate::config::{IdleConfig, IdleSource};
+use crate::toggl::TimeEntry;
+
+const DBUS_TIMEOUT_MS: i32 = 1000;
+
+/// Tells how long the user has been idle.
+pub struct IdleDetector {
+    source: IdleSource,
+    command: Option<String>,
+    conn: Option<Connection>,
+}
+
+impl IdleDetector {
+    pub fn new(source: IdleSource, config: &IdleConfig) -> Result<Self, Error> {
+        let conn = match source {
+            IdleSource::Logind => Some(Connection::get_private(BusType::System)?),
+            IdleSource::Screensaver => Some(Connection::get_private(BusType::Session)?),
+            IdleSource::Command => None,
+        };
+        Ok(IdleDetector {
+            source,
+            command: config.command.clone(),
+            conn,
+        })
+    }
+
+    pub fn idle_time(&self) -> Result<Duration, Error> {
+        match self.source {
+            IdleSource::Logind => self.logind_idle_time(),
+            IdleSource::Screensaver => self.screensaver_idle_time(),
+            IdleSource::Command => self.command_idle_time(),
+        }
+    }
+
+    /// Returns when the user went idle, if they have been idle for at least
+    /// `idle_min` minutes.
+    pub fn idle_since(
+        &self,
+        idle_min: u32,
+        now: DateTime<Local>,
+    ) -> Result<Option<DateTime<Local>>, Error> {
+        let idle = self.idle_time()?;
+        if idle < Duration::minutes(idle_min as i64) {
+            return Ok(None);
+        }
+        Ok(Some(now - idle))
+    }
+
+    /// Reads `IdleHint` and `IdleSinceHint` of the caller's logind session.
+    fn logind_idle_time(&self) -> Result<Duration, Error> {
+        let conn = self.conn.as_ref().unwrap();
+        let session = conn.with_path(
+            "org.freedesktop.login1",
+            "/org/freedesktop/login1/session/auto",
+            DBUS_TIMEOUT_MS,
+        );
+        let idle: bool = session.get("org.freedesktop.login1.Session", "IdleHint")?;
+        if !idle {
+            return Ok(Duration::zero());
+        }
+        let since: u64 = session.get("org.freedesktop.login1.Session", "IdleSinceHint")?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_micros() as u64;
+        Ok(Duration::microseconds(now.saturating_sub(since) as i64))
+    }
+
+    /// Calls `GetSessionIdleTime` of the freedesktop screensaver, which
+    /// returns seconds.
+    fn screensaver_idle_time(&self) -> Result<Duration, Error> {
+        let conn = self.conn.as_ref().unwrap();
+        let msg = Message::new_method_call(
+            "org.freedesktop.ScreenSaver",
+            "/org/freedesktop/ScreenSaver",
+            "org.freedesktop.ScreenSaver",
+            "GetSessionIdleTime",
+        )
+        .map_err(|e| format_err!("{}", e))?;
+        let reply = conn.send_with_reply_and_block(msg, DBUS_TIMEOUT_MS)?;
+        let secs: u32 = reply.read1()?;
+        Ok(Duration::seconds(secs as i64))
+    }
+
+    /// Runs `idle_command`, which prints the idle time in milliseconds like
+    /// `xprintidle` does.
+    fn command_idle_time(&self) -> Result<Duration, Error> {
+        let command = self
+            .command
+            .as_ref()
+            .ok_or_else(|| format_err!("idle command is not configured"))?;
+        let output = Command::new("sh").arg("-c").arg(command).output()?;
+        if !output.status.success() {
+            return Err(format_err!("{} failed", command));
+        }
+        let ms: i64 = String::from_utf8(output.stdout)?.trim().parse()?;
+        Ok(Duration::milliseconds(ms))
+    }
+}
+
+/// Returns when to stop an entry left running since the user went idle at
+/// `since`: then, but never before the entry started.
+pub fn stop_time(entry: &TimeEntry, since: DateTime<Local>) -> DateTime<Local> {
+    since.max(entry.start)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::process::{Child, Stdio};
+    use std::sync::mpsc;
+    use std::thread;
+
+    /// Starts a private session bus with a screensaver reporting `secs` of
+    /// idle time.  Returns the daemon and the address of the bus.
+    fn mock_screensaver(secs: u32) -> (Child, String) {
+        let mut daemon = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address"])
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut address = String::new();
+        BufReader::new(daemon.stdout.take().unwrap())
+            .read_line(&mut address)
+            .unwrap();
+        let address = address.trim().to_string();
+
+        let (ready, wait) = mpsc::channel();
+        let bus = address.clone();
+        thread::spawn(move || {
+            let conn = Connection::open_private(&bus).unwrap();
+            conn.register().unwrap();
+            conn.register_name("org.freedesktop.ScreenSaver", 0)
+                .unwrap();
+            conn.register_object_path("/org/freedesktop/ScreenSaver")
+                .unwrap();
+            ready.send(()).unwrap();
+            loop {
+                for msg in conn.incoming(100) {
+                    if msg.member().as_deref() == Some("GetSessionIdleTime") {
+                        let _ = conn.send(msg.method_return().append1(secs));
+                    }
+                }
+            }
+        });
+        wait.recv().unwrap();
+        (daemon, address)
+    }
+
+    #[test]
+    #[ignore = "requires dbus-daemon"]
+    fn idle_threshold_and_backdated_stop() {
+        let (mut daemon, address) = mock_screensaver(15 * 60);
+        let conn = Connection::open_private(&address).unwrap();
+        conn.register().unwrap();
+        let detector = IdleDetector {
+            source: IdleSource::Screensaver,
+            command: None,
+            conn: Some(conn),
+        };
+        let now = Local::now();
+
+        assert_eq!(detector.idle_time().unwrap(), Duration::minutes(15));
+        assert_eq!(detector.idle_since(20, now).unwrap(), None);
+        let since = detector.idle_since(10, now).unwrap().unwrap();
+        assert_eq!(since, now - Duration::minutes(15));
+
+        // An entry started before going idle stops when idleness began, one
+        // started since then stops as soon as it started.
+        let early = TimeEntry::new(1, "write", None, &[], now - Duration::minutes(40));
+        assert_eq!(stop_time(&early, since), since);
+        let late = TimeEntry::new(2, "write", None, &[], now - Duration::minutes(5));
+        assert_eq!(stop_time(&late, since), late.start);
+
+        let _ = daemon.kill();
+    }
+}
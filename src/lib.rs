@@ -1,4 +1,24 @@
+pub mod budget;
+pub mod catalog;
+pub mod client;
 pub mod config;
+pub mod doctor;
+pub mod duration;
+pub mod goals;
+pub mod history;
+pub mod http;
+pub mod idle;
+pub mod logging;
+pub mod metrics;
 pub mod notifier;
+pub mod plan;
 pub mod pomodoro;
+pub mod report;
+pub mod snooze;
+pub mod status;
+pub mod systemd;
+pub mod team;
 pub mod toggl;
+pub mod tracker;
+#[cfg(feature = "tui")]
+pub mod tui;
@@ -0,0 +1,173 @@
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::net::UnixDatagram;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use chrono::Local;
+use failure::{format_err, Error};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+use crate::config::{self, LogConfig, LogOutput};
+
+const JOURNAL_SOCKET: &str = "/run/systemd/journal/socket";
+
+enum Sink {
+    Journald(UnixDatagram),
+    File(Mutex<File>),
+    Stderr,
+}
+
+struct Logger {
+    level: LevelFilter,
+    /// Module filters, longest path first.
+    modules: Vec<(String, LevelFilter)>,
+    sink: Sink,
+}
+
+fn parse_level(s: &str) -> Result<LevelFilter, Error> {
+    s.parse()
+        .map_err(|_| format_err!("invalid log level: {}", s))
+}
+
+/// Returns true if `target` is `module` or one of its submodules.
+fn is_in_module(target: &str, module: &str) -> bool {
+    target
+        .strip_prefix(module)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+}
+
+/// Maps levels to syslog priorities.
+fn priority(level: Level) -> u8 {
+    match level {
+        Level::Error => 3,
+        Level::Warn => 4,
+        Level::Info => 6,
+        Level::Debug | Level::Trace => 7,
+    }
+}
+
+/// Appends a field in the journal's native format.  Values containing a
+/// newline are length-prefixed.
+fn add_field(buf: &mut Vec<u8>, name: &str, value: &str) {
+    buf.extend_from_slice(name.as_bytes());
+    if value.contains('\n') {
+        buf.push(b'\n');
+        buf.extend_from_slice(&(value.len() as u64).to_le_bytes());
+    } else {
+        buf.push(b'=');
+    }
+    buf.extend_from_slice(value.as_bytes());
+    buf.push(b'\n');
+}
+
+impl Logger {
+    fn level_for(&self, target: &str) -> LevelFilter {
+        self.modules
+            .iter()
+            .find(|(module, _)| is_in_module(target, module))
+            .map_or(self.level, |(_, level)| *level)
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level_for(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        // Logging must never take the daemon down, so write errors are
+        // dropped.
+        match self.sink {
+            Sink::Journald(ref socket) => {
+                let mut buf = Vec::new();
+                add_field(&mut buf, "PRIORITY", &priority(record.level()).to_string());
+                add_field(&mut buf, "MESSAGE", &record.args().to_string());
+                add_field(&mut buf, "SYSLOG_IDENTIFIER", "toggdoro");
+                add_field(&mut buf, "TARGET", record.target());
+                let _ = socket.send_to(&buf, JOURNAL_SOCKET);
+            }
+            Sink::File(ref file) => {
+                let _ = writeln!(
+                    file.lock().unwrap(),
+                    "{} {:<5} {}: {}",
+                    Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+                    record.level(),
+                    record.target(),
+                    record.args()
+                );
+            }
+            Sink::Stderr => {
+                let _ = writeln!(
+                    io::stderr(),
+                    "{:<5} {}: {}",
+                    record.level(),
+                    record.target(),
+                    record.args()
+                );
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Sink::File(ref file) = self.sink {
+            let _ = file.lock().unwrap().flush();
+        }
+    }
+}
+
+/// Returns true when started by systemd, whose journal then captures us.
+fn under_systemd() -> bool {
+    env::var_os("JOURNAL_STREAM").is_some() || env::var_os("INVOCATION_ID").is_some()
+}
+
+fn open_file(config: &LogConfig) -> Result<Sink, Error> {
+    let path = match config.path {
+        Some(ref path) => PathBuf::from(path),
+        None => config::data_dir().join("toggdoro.log"),
+    };
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    Ok(Sink::File(Mutex::new(file)))
+}
+
+/// Installs the global logger.  Must be called once, before anything logs.
+pub fn init(config: &LogConfig) -> Result<(), Error> {
+    let level = parse_level(&config.level)?;
+    let mut modules = config
+        .modules
+        .iter()
+        .map(|(module, level)| Ok((module.clone(), parse_level(level)?)))
+        .collect::<Result<Vec<_>, Error>>()?;
+    modules.sort_by_key(|(module, _)| std::cmp::Reverse(module.len()));
+
+    let output = match config.output {
+        LogOutput::Auto if under_systemd() => LogOutput::Journald,
+        LogOutput::Auto => LogOutput::File,
+        output => output,
+    };
+    let sink = match output {
+        LogOutput::Journald => Sink::Journald(UnixDatagram::unbound()?),
+        LogOutput::Stderr => Sink::Stderr,
+        _ => open_file(config)?,
+    };
+
+    let max_level = modules
+        .iter()
+        .map(|(_, level)| *level)
+        .fold(level, std::cmp::max);
+    let logger = Logger {
+        level,
+        modules,
+        sink,
+    };
+    log::set_logger(Box::leak(Box::new(logger))).map_err(|_| format_err!("logger already set"))?;
+    log::set_max_level(max_level);
+    Ok(())
+}
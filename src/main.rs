@@ -1,24 +1,51 @@
+use std::collections::HashSet;
 use std::io::prelude::*;
+use std::io::{self, BufReader};
+use std::net::{TcpListener, TcpStream};
 use std::os::unix::net::{UnixListener, UnixStream};
-use std::sync::{Arc, RwLock};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Instant;
 use std::{env, fs, process, thread, time};
 
-use chrono::{DateTime, Local};
-use clap::{App, Arg};
-use failure::Error;
+use chrono::{DateTime, Datelike, Local, NaiveDate};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use failure::{format_err, Error};
 use handlebars::Handlebars;
 use lazy_static::lazy_static;
-use regex::Regex;
+use log::{error, info, warn};
 use serde_derive::Serialize;
 use signal_hook::{iterator::Signals, SIGINT, SIGTERM};
 
-use toggdoro::config::{Config, CONFIG};
+use toggdoro::budget;
+use toggdoro::catalog::CATALOG;
+use toggdoro::client;
+use toggdoro::config::{Config, FormatConfig, IdleAction, PomodoroConfig, TrackerConfig, CONFIG};
+use toggdoro::doctor::{self, Verdict};
+use toggdoro::duration;
+use toggdoro::goals::{self, Progress, Tally};
+use toggdoro::history::{self, Interruption, InterruptionKind, Outcome, Record};
+use toggdoro::http::{self, Request};
+use toggdoro::idle::{self, IdleDetector};
+use toggdoro::logging;
+use toggdoro::metrics::{self, Instrumented, METRICS};
 use toggdoro::notifier::dbus::DBusNotifier;
 use toggdoro::notifier::mail::MailNotifier;
 use toggdoro::notifier::slack::SlackNotifier;
 use toggdoro::notifier::Notifier;
-use toggdoro::pomodoro::PomodoroMode;
+use toggdoro::plan::{self, TaskProgress};
+use toggdoro::pomodoro::{self, Classifier, PomodoroMode};
+use toggdoro::report;
+use toggdoro::snooze::Offsets;
+use toggdoro::status::{RecentTask, Status, Today};
+use toggdoro::systemd;
+use toggdoro::team::{self, Team};
 use toggdoro::toggl::{TimeEntry, Toggl};
+use toggdoro::tracker::{self, TimeTracker, TrackerError};
+#[cfg(feature = "tui")]
+use toggdoro::tui;
 
 struct PomodoroState {
     npomodoros: u32,
@@ -27,8 +54,33 @@ struct PomodoroState {
     mode: PomodoroMode,
     description: String,
     project: String,
+    client: String,
+    project_color: String,
     finish_time: DateTime<Local>,
     task_finish_time: Option<DateTime<Local>>,
+    phase: Option<Record>,
+    goals: Option<Progress>,
+    /// Pomodoros counted for goals, loaded when first needed.
+    tally: Option<Tally>,
+    goals_dirty: bool,
+    idle_notified: bool,
+    /// Set when the wall clock jumped, e.g. after resuming from suspend.
+    away: Option<chrono::Duration>,
+    offsets: Offsets,
+    long_break_after: u32,
+    /// Latest status of the leader when following one.
+    leader: Option<Status>,
+    /// Pomodoros done on the running task and its estimate, if it is planned.
+    planned_task: Option<(u32, u32)>,
+    plan_day: Option<NaiveDate>,
+    /// The plan of `plan_day`, loaded when first needed.
+    plan_tasks: Option<Vec<plan::Task>>,
+    /// Pomodoros finished since the start of `plan_day`.
+    plan_pomodoros: Vec<Record>,
+    /// Entries of voided pomodoros, which do not count in the cycle.
+    voided: HashSet<u64>,
+    /// Finished phases whose history append failed, retried on the next.
+    unsaved: Vec<Record>,
 }
 
 impl Default for PomodoroState {
@@ -40,8 +92,25 @@ impl Default for PomodoroState {
             mode: PomodoroMode::Idle,
             description: "".to_string(),
             project: "".to_string(),
+            client: "".to_string(),
+            project_color: "".to_string(),
             finish_time: Local::now(),
             task_finish_time: None,
+            phase: None,
+            goals: None,
+            tally: None,
+            goals_dirty: false,
+            idle_notified: false,
+            away: None,
+            offsets: Default::default(),
+            long_break_after: 0,
+            leader: None,
+            planned_task: None,
+            plan_day: None,
+            plan_tasks: None,
+            plan_pomodoros: Vec::new(),
+            unsaved: Vec::new(),
+            voided: HashSet::new(),
         }
     }
 }
@@ -52,137 +121,300 @@ struct Context {
     remaining_time: String,
     remaining_time_abs: String,
     project: String,
+    client: String,
+    project_color: String,
     description: String,
     project_or_description: String,
     task: String,
+    snoozed: String,
+    paused: bool,
+    task_done: Option<u32>,
+    task_estimate: Option<u32>,
+    today: u32,
+    daily_goal: u32,
+    week: u32,
+    streak: u32,
 }
 
+impl Context {
+    /// A context with every field set, for test-rendering templates.
+    fn sample() -> Self {
+        Context {
+            count: 2,
+            remaining_time: "12:34".to_string(),
+            remaining_time_abs: "12:34".to_string(),
+            project: "Project".to_string(),
+            client: "Client".to_string(),
+            project_color: "#06aaf5".to_string(),
+            description: "Description".to_string(),
+            project_or_description: "Project".to_string(),
+            task: "|05:00".to_string(),
+            snoozed: "+5m".to_string(),
+            paused: false,
+            task_done: Some(1),
+            task_estimate: Some(3),
+            today: 4,
+            daily_goal: 8,
+            week: 20,
+            streak: 3,
+        }
+    }
+}
+
+/// A wall clock jump larger than this between ticks is taken as a resume
+/// from suspend or a clock change.
+const CLOCK_JUMP_SECS: i64 = 60;
+
+/// Number of tasks `recent` lists.
+const RECENT_TASKS: usize = 20;
+
+/// Description of the entries started by the `break` command.
+const BREAK_DESCRIPTION: &str = "Pomodoro Break";
+
+/// Commands the HTTP API accepts as `POST /<command>`.
+const API_COMMANDS: &[&str] = &[
+    "start",
+    "stop",
+    "break",
+    "skip",
+    "snooze",
+    "pause",
+    "resume",
+    "interrupt",
+    "abandon",
+    "plan",
+];
+
+/// The single-page dashboard served on `/`.
+const DASHBOARD: &str = include_str!("dashboard.html");
+
+/// Delay before reconnecting to the leader.
+const SYNC_RETRY_SECS: u64 = 10;
+
+/// Interval of the comments that keep idle event streams open.
+const KEEPALIVE_SECS: u64 = 15;
+
+/// Label of pomodoros without a project in metrics.
+const NO_PROJECT: &str = "(no project)";
+
+/// The watchdog is no longer fed once the monitor has not finished a tick
+/// for this long, well above the time its tracker requests may take.
+const MAX_TICK_SECS: u64 = 300;
+
 lazy_static! {
     static ref POMODORO_STATE: RwLock<PomodoroState> = RwLock::new(Default::default());
+    static ref SUBSCRIBERS: Mutex<Vec<Sender<String>>> = Mutex::new(Vec::new());
+    /// When the monitor last finished a tick.
+    static ref LAST_TICK: Mutex<Instant> = Mutex::new(Instant::now());
 }
 
-fn mode_of_entry(entry: &TimeEntry) -> PomodoroMode {
-    if entry.description == "Pomodoro Break" {
-        return PomodoroMode::Break;
-    }
-    if entry.tags.iter().any(|x| x == "pomodoro-break") {
-        PomodoroMode::Break
-    } else {
-        PomodoroMode::Work
+/// Set by SIGTERM or SIGINT to stop accepting connections.
+static SHUTDOWN: AtomicBool = AtomicBool::new(false);
+
+/// Saves the running phase, so a restart keeps its interruptions.
+fn save_phase(state: &PomodoroState) {
+    if let Err(e) = history::save_running(state.phase.as_ref()) {
+        warn!("saving the running phase failed: {}", e);
     }
 }
 
-fn task_min(entry: &TimeEntry) -> Result<Option<u32>, Error> {
-    let re = Regex::new(r"^(\d+)min$")?;
-    for tag in &entry.tags {
-        if let Some(cap) = re.captures(tag) {
-            return Ok(Some(cap[1].parse()?));
+/// Records the phase being tracked, if any, as finished.
+fn finish_phase(state: &mut PomodoroState, entries: &[TimeEntry], config: &Config) {
+    if let Some(mut record) = state.phase.take() {
+        save_phase(state);
+        let end = entries
+            .iter()
+            .find(|x| x.id == record.entry_id)
+            .and_then(|x| x.stop)
+            .unwrap_or_else(Local::now);
+        record.finish(end);
+        info!(
+            "{:?} {} finished: {:?}",
+            record.mode, record.count, record.outcome
+        );
+        if record.is_pomodoro() {
+            METRICS.write().unwrap().add_pomodoro(
+                pomodoro::day_of(&record.start, config.pomodoro.day_start_hour),
+                record.project.as_deref().unwrap_or(NO_PROJECT),
+            );
+        }
+        if let Some(ref mut tally) = state.tally {
+            tally.add(&record);
+        }
+        if record.is_pomodoro() {
+            state.plan_pomodoros.push(record.clone());
+        }
+        state.unsaved.push(record);
+        state.goals_dirty = true;
+    }
+    // A failed append must not cost the record nor the rest of the tick.
+    while let Some(record) = state.unsaved.first() {
+        if let Err(e) = history::append(&config.history, record) {
+            error!("recording the phase failed: {}", e);
+            break;
         }
+        state.unsaved.remove(0);
     }
-    Ok(None)
 }
 
-fn update(toggl: &Toggl, notifiers: &Vec<Box<dyn Notifier>>) -> Result<(), Error> {
+fn update_pomodoro(entries: &[TimeEntry], notifiers: &Vec<Box<dyn Notifier>>) -> Result<(), Error> {
     let config = CONFIG.read().unwrap();
-    let pomodoro_config = &config.pomodoro;
-    let entries = toggl.time_entries()?;
+    let catalog = CATALOG.read().unwrap();
     let mut state = POMODORO_STATE.write().unwrap();
-    let mut history: Vec<(PomodoroMode, i64)> = Vec::new();
+    let away = state.away.take();
 
     state.mode = PomodoroMode::Idle;
 
     if let Some(latest_entry) = entries.first() {
-        if latest_entry.duration >= 0 {
-            return Ok(());
-        }
-        let mut last_start = &latest_entry.start;
-        let mut extra_task_duration = 0;
-        state.mode = mode_of_entry(latest_entry);
-
-        if state.mode == PomodoroMode::Work {
-            for x in &entries[1..] {
-                if mode_of_entry(x) == PomodoroMode::Break {
-                    continue;
-                }
-                if latest_entry.description == x.description
-                    && latest_entry.project_id == x.project_id
-                    && latest_entry.tags == x.tags
-                {
-                    extra_task_duration += x.duration;
-                } else {
-                    break;
-                }
-            }
-        }
-
-        for x in &entries[1..] {
-            let mode = mode_of_entry(x);
-
-            if let Some(stop) = x.stop {
-                if (*last_start - stop).num_seconds() > 120 {
-                    break;
+        let phase =
+            match pomodoro::current_phase(&config.pomodoro, &catalog, entries, &state.voided)? {
+                Some(phase) => phase,
+                None => {
+                    finish_phase(&mut state, entries, &config);
+                    return Ok(());
                 }
-            } else {
-                break;
-            }
+            };
+        let latest_mode = phase.mode;
+        let lengths = phase.lengths;
+        let planned = phase.planned_secs;
+        state.mode = latest_mode;
+        state.npomodoros = phase.count;
+        state.long_break_after = lengths.long_break_after;
+        state.description.clone_from(&latest_entry.description);
+        state.project = catalog.project_name(latest_entry).unwrap_or_default();
+        state.client = catalog.client_name(latest_entry).unwrap_or_default();
+        state.project_color = catalog.project_color(latest_entry).unwrap_or_default();
+        state.finish_time = phase.finish_time;
 
-            match history.last_mut() {
-                Some(ref mut v) if v.0 == mode => **v = (v.0, v.1 + x.duration),
-                _ => history.push((mode, x.duration)),
+        let continued = phase.continued;
+        let same_phase = state
+            .phase
+            .as_ref()
+            .is_some_and(|x| x.mode == latest_mode && (x.entry_id == latest_entry.id || continued));
+        let mut new_entry = false;
+        if !same_phase {
+            finish_phase(&mut state, entries, &config);
+            info!(
+                "{:?} {} started: {}",
+                latest_mode, state.npomodoros, latest_entry.description
+            );
+            state.phase = Some(Record {
+                entry_id: latest_entry.id,
+                entry_ids: vec![latest_entry.id],
+                mode: latest_mode,
+                outcome: Outcome::Completed,
+                description: latest_entry.description.clone(),
+                project: catalog.project_name(latest_entry),
+                tags: latest_entry.tags.clone(),
+                start: state.finish_time - chrono::Duration::seconds(planned),
+                end: state.finish_time,
+                planned_secs: planned,
+                actual_secs: 0,
+                count: state.npomodoros,
+                long_break: latest_mode == PomodoroMode::Break
+                    && state.npomodoros >= lengths.long_break_after,
+                notifications: 0,
+                interrupted: false,
+                interruptions: Vec::new(),
+                voided: false,
+            });
+            save_phase(&state);
+        } else if let Some(ref mut record) = state.phase {
+            new_entry = !record.entry_ids.contains(&latest_entry.id);
+            if new_entry {
+                record.entry_ids.push(latest_entry.id);
             }
+            record.entry_id = latest_entry.id;
+            record.description.clone_from(&latest_entry.description);
+            record.project = catalog.project_name(latest_entry);
+            record.tags.clone_from(&latest_entry.tags);
+        }
+        if new_entry {
+            save_phase(&state);
+        }
+        state.task_finish_time =
+            budget::task_budget(&config.budget, latest_entry, &catalog)?.map(|x| {
+                budget::finish_time(
+                    x,
+                    entries,
+                    latest_entry,
+                    config.pomodoro.day_start_hour,
+                    Local::now(),
+                )
+            });
 
-            if let Some(&(PomodoroMode::Break, d)) = history.last() {
-                if d >= (pomodoro_config.long_break_min as i64 * 60) {
-                    history.pop();
-                    break;
-                }
+        if let Some(phase_start) = state.phase.as_ref().map(|x| x.start) {
+            if state.offsets.for_phase(phase_start) {
+                state.offsets.save()?;
             }
-
-            last_start = &x.start;
         }
-        state.npomodoros = (history.len() / 2 + 1) as u32;
-        let mut duration = {
-            if mode_of_entry(latest_entry) == PomodoroMode::Break {
-                if state.npomodoros >= pomodoro_config.long_break_after {
-                    pomodoro_config.long_break_min as i64 * 60
-                } else {
-                    pomodoro_config.short_break_min as i64 * 60
-                }
-            } else {
-                pomodoro_config.pomodoro_min as i64 * 60
-            }
-        };
-        if let Some(v) = history.first() {
-            if v.0 == mode_of_entry(latest_entry) {
-                duration -= v.1;
-            }
+        let offset = state.offsets.total(Local::now());
+        state.finish_time += offset;
+        state.task_finish_time = state.task_finish_time.map(|x| x + offset);
+        if let Some(ref mut record) = state.phase {
+            record.planned_secs = planned + offset.num_seconds();
+        }
+
+        // Followers count and end phases with the leader.
+        let leader = state
+            .leader
+            .as_ref()
+            .filter(|x| x.mode == latest_mode)
+            .map(|x| (x.count, x.finish_time));
+        if let Some((count, finish_time)) = leader {
+            state.npomodoros = count;
+            state.finish_time = finish_time.unwrap_or(state.finish_time);
         }
-        state.description.clone_from(&latest_entry.description);
-        state.project = latest_entry.project_name.clone().unwrap_or_default();
-        state.finish_time = latest_entry.start + chrono::Duration::seconds(duration as i64);
-        state.task_finish_time = task_min(latest_entry)?.map(|x| {
-            latest_entry.start
-                + chrono::Duration::seconds(x as i64 * 60 - extra_task_duration)
-        });
 
         // notification
         let now = Local::now();
         let duration = state.finish_time - now;
         let dur_secs = duration.num_seconds();
 
+        // After a suspend or clock jump, send one summary instead of firing
+        // every reminder that became due while away.
+        if let Some(away) = away {
+            let task_over = state.task_finish_time.is_some_and(|x| x < now);
+            if dur_secs < 0 || task_over {
+                let ended = if dur_secs < 0 {
+                    state.finish_time
+                } else {
+                    state.task_finish_time.unwrap()
+                };
+                let message = format!(
+                    "You were away for {}h{:02}m; {:?} {} at {}",
+                    away.num_hours(),
+                    away.num_minutes() % 60,
+                    latest_mode,
+                    if dur_secs < 0 {
+                        "ended"
+                    } else {
+                        "task ran over"
+                    },
+                    ended.format("%H:%M")
+                );
+                for n in notifiers {
+                    n.message(&message)?;
+                }
+                if dur_secs < 0 {
+                    state.nnotifications = 3;
+                }
+                if task_over {
+                    state.ntnotifications = 3;
+                }
+                if let Some(ref mut record) = state.phase {
+                    record.notifications += 1;
+                }
+                return Ok(());
+            }
+        }
+
         if dur_secs < 0 {
             let (next, min) = {
-                if mode_of_entry(latest_entry) == PomodoroMode::Break {
-                    (PomodoroMode::Work, pomodoro_config.pomodoro_min)
+                if latest_mode == PomodoroMode::Break {
+                    (PomodoroMode::Work, lengths.pomodoro_min)
                 } else {
-                    (
-                        PomodoroMode::Break,
-                        if state.npomodoros >= pomodoro_config.long_break_after {
-                            pomodoro_config.long_break_min
-                        } else {
-                            pomodoro_config.short_break_min
-                        },
-                    )
+                    (PomodoroMode::Break, lengths.break_min(state.npomodoros))
                 }
             };
 
@@ -194,6 +426,9 @@ fn update(toggl: &Toggl, notifiers: &Vec<Box<dyn Notifier>>) -> Result<(), Error
                     n.notify(next, min)?;
                 }
                 state.nnotifications += 1;
+                if let Some(ref mut record) = state.phase {
+                    record.notifications += 1;
+                }
             }
             state.ntnotifications = 0;
         } else {
@@ -211,51 +446,675 @@ fn update(toggl: &Toggl, notifiers: &Vec<Box<dyn Notifier>>) -> Result<(), Error
                         n.notify(PomodoroMode::Work, duration.num_minutes() as u32)?;
                     }
                     state.ntnotifications += 1;
+                    if let Some(ref mut record) = state.phase {
+                        record.notifications += 1;
+                    }
                 }
             } else {
                 state.ntnotifications = 0;
             }
         }
+    } else {
+        finish_phase(&mut state, entries, &config);
     }
     Ok(())
 }
 
-fn monitor() {
+fn update_goals(notifiers: &Vec<Box<dyn Notifier>>) -> Result<(), Error> {
     let config = CONFIG.read().unwrap();
+    if !config.goals.is_enabled() {
+        return Ok(());
+    }
+    let mut state = POMODORO_STATE.write().unwrap();
+    let now = Local::now();
+    let today = pomodoro::day_of(&now, config.pomodoro.day_start_hour);
+    if !state.goals_dirty && state.goals.as_ref().is_some_and(|x| x.day == today) {
+        return Ok(());
+    }
 
-    let interval = time::Duration::from_secs(3);
-    let toggl = Toggl::new(config.toggl_token.to_string());
-    let mut notifiers: Vec<Box<dyn Notifier>> = Vec::new();
+    if state.tally.is_none() {
+        state.tally = Some(Tally::load(
+            &config.history,
+            config.pomodoro.day_start_hour,
+            now,
+        )?);
+    }
+    let progress = state.tally.as_ref().unwrap().progress(&config.goals, now);
+    if let Some(ref before) = state.goals {
+        for message in goals::reached(&config.goals, before, &progress) {
+            for n in notifiers {
+                n.message(&message)?;
+            }
+        }
+    }
+    state.goals = Some(progress);
+    state.goals_dirty = false;
+    Ok(())
+}
+
+/// Matches the plan of `day` against the tracker and the local history.
+fn plan_progress(
+    config: &Config,
+    tracker: &dyn TimeTracker,
+    day: NaiveDate,
+) -> Result<Vec<TaskProgress>, Error> {
+    let tasks = plan::load(&config.plan, day)?;
+    if tasks.is_empty() {
+        return Ok(Vec::new());
+    }
+    let day_start_hour = config.pomodoro.day_start_hour;
+    let start = pomodoro::start_of_day(day, day_start_hour)
+        .ok_or_else(|| format_err!("invalid date: {}", day))?;
+    let end = pomodoro::start_of_day(day + chrono::Duration::days(1), day_start_hour)
+        .ok_or_else(|| format_err!("invalid date: {}", day))?;
+    let entries = tracker.time_entries_between(start, end)?;
+    let records = history::load(&config.history, start, end)?;
+    Ok(plan::progress(
+        &tasks,
+        &entries,
+        &records,
+        &CATALOG.read().unwrap(),
+        day,
+        day_start_hour,
+        Local::now(),
+    ))
+}
+
+fn update_plan(
+    tracker: &dyn TimeTracker,
+    entries: &[TimeEntry],
+    notifiers: &Vec<Box<dyn Notifier>>,
+) -> Result<(), Error> {
+    let config = CONFIG.read().unwrap();
+    let today = pomodoro::day_of(&Local::now(), config.pomodoro.day_start_hour);
+
+    let previous = POMODORO_STATE.read().unwrap().plan_day;
+    if previous != Some(today) {
+        if let Some(day) = previous {
+            let progress = plan_progress(&config, tracker, day)?;
+            if !progress.is_empty() {
+                let message = plan::summary(day, &progress);
+                for n in notifiers {
+                    n.message(&message)?;
+                }
+            }
+        }
+        let start = pomodoro::start_of_day(today, config.pomodoro.day_start_hour)
+            .ok_or_else(|| format_err!("invalid date: {}", today))?;
+        let records = history::load(&config.history, start, Local::now())?;
+        let mut state = POMODORO_STATE.write().unwrap();
+        state.plan_day = Some(today);
+        state.plan_tasks = None;
+        state.plan_pomodoros = records.into_iter().filter(|x| x.is_pomodoro()).collect();
+    }
+    if POMODORO_STATE.read().unwrap().plan_tasks.is_none() {
+        let tasks = plan::load(&config.plan, today)?;
+        POMODORO_STATE.write().unwrap().plan_tasks = Some(tasks);
+    }
+
+    let catalog = CATALOG.read().unwrap();
+    let mut state = POMODORO_STATE.write().unwrap();
+    let planned_task = entries
+        .first()
+        .filter(|x| x.duration < 0)
+        .and_then(|entry| {
+            state
+                .plan_tasks
+                .iter()
+                .flatten()
+                .find(|x| x.matches(entry, &catalog))
+        })
+        .map(|task| {
+            let done = state
+                .plan_pomodoros
+                .iter()
+                .filter(|x| task.matches_record(x))
+                .count() as u32;
+            (done, task.estimate)
+        });
+    state.planned_task = planned_task;
+    Ok(())
+}
+
+fn update(tracker: &dyn TimeTracker, notifiers: &Vec<Box<dyn Notifier>>) -> Result<(), Error> {
+    let entries = tracker.time_entries()?;
+    update_pomodoro(&entries, notifiers)?;
+    update_goals(notifiers)?;
+    update_plan(tracker, &entries, notifiers)
+}
+
+fn update_metrics() {
+    let config = CONFIG.read().unwrap();
+    let state = POMODORO_STATE.read().unwrap();
+    let now = Local::now();
+    let remaining_secs = if state.mode == PomodoroMode::Idle {
+        0
+    } else {
+        (state.finish_time - now).num_seconds()
+    };
+    METRICS.write().unwrap().set_phase(
+        state.mode,
+        remaining_secs,
+        pomodoro::day_of(&now, config.pomodoro.day_start_hour),
+    );
+}
+
+fn check_idle(
+    detector: &IdleDetector,
+    tracker: &dyn TimeTracker,
+    notifiers: &Vec<Box<dyn Notifier>>,
+) -> Result<(), Error> {
+    let config = CONFIG.read().unwrap();
+    let since = detector.idle_since(config.idle.idle_min, Local::now())?;
+    let since = {
+        let mut state = POMODORO_STATE.write().unwrap();
+        match since {
+            Some(since) if state.mode == PomodoroMode::Work && !state.idle_notified => since,
+            Some(_) => return Ok(()),
+            None => {
+                state.idle_notified = false;
+                return Ok(());
+            }
+        }
+    };
+
+    // Talk to the tracker with no lock held; the state is updated after.
+    let mut message = format!("Idle since {}", since.format("%H:%M"));
+    if config.idle.action == IdleAction::Stop {
+        if let Some(entry) = tracker.current_time_entry()? {
+            let entry = tracker.stop(&entry, Some(idle::stop_time(&entry, since)))?;
+            message += &format!(", stopped {}", entry.description);
+        }
+    }
+    {
+        let mut state = POMODORO_STATE.write().unwrap();
+        state.idle_notified = true;
+        if config.idle.action == IdleAction::Interrupt {
+            if let Some(ref mut record) = state.phase {
+                record.interrupted = true;
+                message += ", pomodoro interrupted";
+                save_phase(&state);
+            }
+        }
+    }
+    for n in notifiers {
+        n.message(&message)?;
+    }
+    Ok(())
+}
+
+fn notifiers(config: &Config) -> Result<Vec<Instrumented>, Error> {
+    let mut notifiers = Vec::new();
     if config.notification.dbus {
-        notifiers.push(Box::new(DBusNotifier::new().unwrap()));
+        notifiers.push(Instrumented::new("dbus", Box::new(DBusNotifier::new()?)));
     }
     if let Some(url) = config.notification.slack.as_ref() {
-        notifiers.push(Box::new(SlackNotifier::new(url).unwrap()));
+        notifiers.push(Instrumented::new(
+            "slack",
+            Box::new(SlackNotifier::new(url)?),
+        ));
     }
     if let Some(to) = config.notification.mail.as_ref() {
-        notifiers.push(Box::new(
-            MailNotifier::new("toggdoro@localhost", to).unwrap(),
+        notifiers.push(Instrumented::new(
+            "mail",
+            Box::new(MailNotifier::new("toggdoro@localhost", to)?),
         ));
     }
+    Ok(notifiers)
+}
+
+fn monitor(tracker: Box<dyn TimeTracker>) {
+    let config = CONFIG.read().unwrap();
+
+    let interval = time::Duration::from_secs(3);
+    let notifiers: Vec<Box<dyn Notifier>> = notifiers(&config)
+        .unwrap()
+        .into_iter()
+        .map(|x| Box::new(x) as Box<dyn Notifier>)
+        .collect();
+    let idle_detector = config
+        .idle
+        .source
+        .and_then(|x| match IdleDetector::new(x, &config.idle) {
+            Ok(detector) => Some(detector),
+            Err(e) => {
+                error!("idle detection disabled: {}", e);
+                None
+            }
+        });
+    let mut last_wall = Local::now();
+    let mut last_mono = Instant::now();
+    let mut last_status = None;
+    loop {
+        // The monotonic clock stops during suspend while the wall clock
+        // does not, so their difference reveals a resume or clock jump.
+        let (wall, mono) = (Local::now(), Instant::now());
+        let jump = (wall - last_wall)
+            - chrono::Duration::from_std(mono - last_mono)
+                .unwrap_or_else(|_| chrono::Duration::zero());
+        last_wall = wall;
+        last_mono = mono;
+        if jump.num_seconds().abs() > CLOCK_JUMP_SECS {
+            POMODORO_STATE.write().unwrap().away = Some(jump.abs());
+            CATALOG.write().unwrap().invalidate();
+        }
+
+        if CATALOG.read().unwrap().is_stale(config.catalog_refresh_min) {
+            let update = tracker.fetch_catalog();
+            CATALOG.write().unwrap().apply(update);
+        }
+        if let Err(e) = update(tracker.as_ref(), &notifiers) {
+            error!("update failed: {}", e);
+        }
+        update_metrics();
+        *LAST_TICK.lock().unwrap() = Instant::now();
+        let status = status();
+        if last_status.as_ref() != Some(&status) {
+            if let Err(e) = broadcast(&status) {
+                warn!("broadcast failed: {}", e);
+            }
+            let message = match status.mode {
+                PomodoroMode::Idle => "STATUS=Idle".to_string(),
+                mode => format!("STATUS={:?} {}: {}", mode, status.count, status.description),
+            };
+            if let Err(e) = systemd::notify(&message) {
+                warn!("status notification failed: {}", e);
+            }
+            last_status = Some(status);
+        }
+        if let Some(ref detector) = idle_detector {
+            if let Err(e) = check_idle(detector, tracker.as_ref(), &notifiers) {
+                error!("idle check failed: {}", e);
+            }
+        }
+        thread::sleep(interval);
+    }
+}
+
+/// Feeds the systemd watchdog every `interval` while the monitor keeps
+/// ticking.  Runs apart from the monitor, whose ticks block on tracker
+/// requests.
+fn watchdog(interval: time::Duration) {
+    loop {
+        let since_tick = LAST_TICK.lock().unwrap().elapsed();
+        if since_tick < time::Duration::from_secs(MAX_TICK_SECS) {
+            if let Err(e) = systemd::notify("WATCHDOG=1") {
+                warn!("watchdog notification failed: {}", e);
+            }
+        } else {
+            error!("monitor stuck for {}s", since_tick.as_secs());
+        }
+        thread::sleep(interval);
+    }
+}
+
+/// Mirrors a phase boundary of the leader by starting or stopping entries.
+fn follow_status(config: &Config, tracker: &dyn TimeTracker, status: Status) -> Result<(), Error> {
+    let (boundary, in_step) = {
+        let mut state = POMODORO_STATE.write().unwrap();
+        let boundary = state
+            .leader
+            .as_ref()
+            .is_none_or(|x| x.mode != status.mode || x.description != status.description);
+        let in_step = state.mode == status.mode
+            && (status.mode != PomodoroMode::Work || state.description == status.description);
+        state.leader = Some(status.clone());
+        (boundary, in_step)
+    };
+    if !boundary || in_step {
+        return Ok(());
+    }
+
+    match status.mode {
+        PomodoroMode::Work => {
+            let project = CATALOG
+                .read()
+                .unwrap()
+                .find_project(&status.project)
+                .map(|x| x.name.clone());
+            if let Some(entry) = tracker.current_time_entry()? {
+                tracker.stop(&entry, None)?;
+            }
+            tracker.start(
+                &CATALOG.read().unwrap(),
+                &status.description,
+                project.as_deref(),
+                &[],
+            )?;
+        }
+        PomodoroMode::Break => {
+            start_break(&config.pomodoro, tracker)?;
+        }
+        PomodoroMode::Idle => {
+            if let Some(entry) = tracker.current_time_entry()? {
+                tracker.stop(&entry, None)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Follows the event stream of the leader until it ends.  A leader silent
+/// for several keepalive intervals is taken as lost.
+fn follow_leader(config: &Config, tracker: &dyn TimeTracker, url: &str) -> Result<(), Error> {
+    let client = reqwest::Client::builder()
+        .timeout(time::Duration::from_secs(3 * KEEPALIVE_SECS))
+        .build()?;
+    let mut req = client.get(&format!("{}/events", url.trim_end_matches('/')));
+    if let Some(ref token) = config.sync.token {
+        req = req.bearer_auth(token);
+    }
+    let res = req.send()?.error_for_status()?;
+    for line in BufReader::new(res).lines() {
+        if let Some(data) = line?.strip_prefix("data: ") {
+            follow_status(config, tracker, serde_json::from_str(data)?)?;
+        }
+    }
+    Ok(())
+}
+
+fn sync_monitor(tracker: Box<dyn TimeTracker>) {
+    let config = CONFIG.read().unwrap();
+
+    let url = config.sync.leader.as_ref().unwrap();
+    loop {
+        if let Err(e) = follow_leader(&config, tracker.as_ref(), url) {
+            warn!("lost leader {}: {}", url, e);
+        }
+        POMODORO_STATE.write().unwrap().leader = None;
+        thread::sleep(time::Duration::from_secs(SYNC_RETRY_SECS));
+    }
+}
+
+fn team_monitor(slack: Option<SlackNotifier>) {
+    let config = CONFIG.read().unwrap();
+
+    let interval = time::Duration::from_secs(config.team.refresh_sec as u64);
+    let mut team = Team::new(&config.team);
+    let slack = slack.map(|x| Instrumented::new("slack", Box::new(x)));
     loop {
-        if let Err(e) = update(&toggl, &notifiers) {
-            println!("{}", e);
+        let members = team.refresh(&config);
+        let before = team::BOARD.read().unwrap().clone();
+        if !before.is_empty() && team::is_changed(&before, &members) {
+            if let Some(ref slack) = slack {
+                if let Err(e) = slack.message(&team::board(&members, Local::now())) {
+                    warn!("team board post failed: {}", e);
+                }
+            }
         }
+        *team::BOARD.write().unwrap() = members;
         thread::sleep(interval);
     }
 }
 
+/// Reads a single command line from the client.  Clients that only want the
+/// status line may send nothing at all, so a short read timeout or EOF is
+/// treated as an empty command.
+fn read_command(stream: &UnixStream) -> Result<String, Error> {
+    stream.set_read_timeout(Some(time::Duration::from_millis(100)))?;
+    let mut line = String::new();
+    if let Err(e) = BufReader::new(stream).read_line(&mut line) {
+        match e.kind() {
+            std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut => (),
+            _ => return Err(e.into()),
+        }
+    }
+    stream.set_read_timeout(None)?;
+    Ok(line.trim().to_string())
+}
+
+/// Stops the running entry and starts one `break_match` recognizes as a
+/// break.
+fn start_break(config: &PomodoroConfig, tracker: &dyn TimeTracker) -> Result<TimeEntry, Error> {
+    if let Some(entry) = tracker.current_time_entry()? {
+        tracker.stop(&entry, None)?;
+    }
+    tracker.start(
+        &CATALOG.read().unwrap(),
+        BREAK_DESCRIPTION,
+        config.break_match.projects.first().map(|x| x.as_str()),
+        &config.break_match.tags,
+    )
+}
+
+/// Stops the running entry and restarts the latest work entry.
+fn resume_work(config: &PomodoroConfig, tracker: &dyn TimeTracker) -> Result<TimeEntry, Error> {
+    let entries = tracker.time_entries()?;
+    let (description, project, tags) = {
+        let catalog = CATALOG.read().unwrap();
+        let classifier = Classifier::new(config, &catalog);
+        let entry = entries
+            .iter()
+            .find(|x| classifier.mode_of(x) == Some(PomodoroMode::Work))
+            .ok_or_else(|| format_err!("no work entry to resume"))?;
+        (
+            entry.description.clone(),
+            catalog.project_name(entry),
+            entry.tags.clone(),
+        )
+    };
+    if let Some(entry) = tracker.current_time_entry()? {
+        tracker.stop(&entry, None)?;
+    }
+    tracker.start(
+        &CATALOG.read().unwrap(),
+        &description,
+        project.as_deref(),
+        &tags,
+    )
+}
+
+fn handle_command(stream: &mut dyn Write, command: &str) -> Result<(), Error> {
+    let config = CONFIG.read().unwrap();
+    let tracker = tracker::from_config(&config)?;
+    let (name, args) = match command.find(' ') {
+        Some(i) => (&command[..i], command[i + 1..].trim()),
+        None => (command, ""),
+    };
+
+    match name {
+        "start" => {
+            let (description, project, tags) = if args == "next" {
+                let today = pomodoro::day_of(&Local::now(), config.pomodoro.day_start_hour);
+                let progress = plan_progress(&config, tracker.as_ref(), today)?;
+                let task = &plan::next(&progress)
+                    .ok_or_else(|| format_err!("no planned task left"))?
+                    .task;
+                (
+                    task.description.clone(),
+                    task.project.clone(),
+                    task.tags.clone(),
+                )
+            } else {
+                plan::parse_task_args(args)
+            };
+            let entry = tracker.start(
+                &CATALOG.read().unwrap(),
+                &description,
+                project.as_deref(),
+                &tags,
+            )?;
+            writeln!(stream, "started {}", entry.description)?;
+        }
+        "stop" => match tracker.current_time_entry()? {
+            Some(entry) => {
+                tracker.stop(&entry, None)?;
+                writeln!(stream, "stopped {}", entry.description)?;
+            }
+            None => writeln!(stream, "no running entry")?,
+        },
+        "status" if args == "json" => {
+            writeln!(stream, "{}", serde_json::to_string(&status())?)?;
+        }
+        "team" if args == "json" => {
+            let board = team::BOARD.read().unwrap();
+            writeln!(stream, "{}", serde_json::to_string(&*board)?)?;
+        }
+        "team" => write!(
+            stream,
+            "{}",
+            team::board(&team::BOARD.read().unwrap(), Local::now())
+        )?,
+        "today" => writeln!(stream, "{}", serde_json::to_string(&today()?)?)?,
+        "recent" => {
+            let entries = tracker.time_entries()?;
+            let catalog = CATALOG.read().unwrap();
+            let classifier = Classifier::new(&config.pomodoro, &catalog);
+            let mut tasks: Vec<RecentTask> = Vec::new();
+            for entry in entries {
+                if classifier.mode_of(&entry) != Some(PomodoroMode::Work) {
+                    continue;
+                }
+                let task = RecentTask {
+                    description: entry.description.clone(),
+                    project: catalog.project_name(&entry),
+                    tags: entry.tags.clone(),
+                };
+                if !tasks.contains(&task) {
+                    tasks.push(task);
+                }
+                if tasks.len() >= RECENT_TASKS {
+                    break;
+                }
+            }
+            writeln!(stream, "{}", serde_json::to_string(&tasks)?)?;
+        }
+        "break" => {
+            let entry = start_break(&config.pomodoro, tracker.as_ref())?;
+            writeln!(stream, "started {}", entry.description)?;
+        }
+        "skip" => {
+            let mode = POMODORO_STATE.read().unwrap().mode;
+            let entry = match mode {
+                PomodoroMode::Work => start_break(&config.pomodoro, tracker.as_ref())?,
+                PomodoroMode::Break => resume_work(&config.pomodoro, tracker.as_ref())?,
+                PomodoroMode::Idle => return Err(format_err!("no running phase")),
+            };
+            writeln!(stream, "started {}", entry.description)?;
+        }
+        "plan" => {
+            let today = pomodoro::day_of(&Local::now(), config.pomodoro.day_start_hour);
+            match args.strip_prefix("add ") {
+                Some(line) => {
+                    let task = plan::Task::parse(line)?;
+                    plan::add(&config.plan, today, &task)?;
+                    POMODORO_STATE.write().unwrap().plan_tasks = None;
+                    writeln!(stream, "planned {}", task.to_line())?;
+                }
+                None if args.is_empty() => {
+                    let progress = plan_progress(&config, tracker.as_ref(), today)?;
+                    write!(
+                        stream,
+                        "{}",
+                        plan::review(&progress, config.pomodoro.pomodoro_min)
+                    )?;
+                }
+                None => return Err(format_err!("usage: plan [add <estimate> <task>]")),
+            }
+        }
+        "snooze" => {
+            let d = duration::parse(args)
+                .or_else(|| args.parse().ok().map(chrono::Duration::minutes))
+                .ok_or_else(|| format_err!("invalid duration: {}", args))?;
+            let mut state = POMODORO_STATE.write().unwrap();
+            if state.mode == PomodoroMode::Idle {
+                return Err(format_err!("no running phase"));
+            }
+            state.offsets.snooze(d)?;
+            state.offsets.save()?;
+            state.finish_time += d;
+            writeln!(stream, "snoozed {} min", d.num_minutes())?;
+        }
+        "pause" | "resume" => {
+            let mut state = POMODORO_STATE.write().unwrap();
+            if state.mode == PomodoroMode::Idle {
+                return Err(format_err!("no running phase"));
+            }
+            if name == "pause" {
+                state.offsets.pause(Local::now())?;
+            } else {
+                state.offsets.resume(Local::now())?;
+            }
+            state.offsets.save()?;
+            writeln!(stream, "{}d", name)?;
+        }
+        "interrupt" => {
+            let (kind, note) = match args.find(' ') {
+                Some(i) => (&args[..i], Some(args[i + 1..].trim().to_string())),
+                None => (args, None),
+            };
+            let kind = match kind {
+                "internal" => InterruptionKind::Internal,
+                "external" => InterruptionKind::External,
+                _ => return Err(format_err!("usage: interrupt internal|external [note]")),
+            };
+            let mut state = POMODORO_STATE.write().unwrap();
+            let record = state
+                .phase
+                .as_mut()
+                .ok_or_else(|| format_err!("no running phase"))?;
+            record.interruptions.push(Interruption {
+                kind,
+                at: Local::now(),
+                note,
+            });
+            writeln!(
+                stream,
+                "{:?} interruptions: {}",
+                kind,
+                record.count_interruptions(kind)
+            )?;
+            save_phase(&state);
+        }
+        "abandon" => {
+            {
+                let mut state = POMODORO_STATE.write().unwrap();
+                let entry_ids: Vec<u64> = match state.phase {
+                    Some(ref mut record) if record.mode == PomodoroMode::Work => {
+                        record.voided = true;
+                        record.all_entry_ids().collect()
+                    }
+                    _ => return Err(format_err!("no running pomodoro")),
+                };
+                state.voided.extend(entry_ids);
+                save_phase(&state);
+            }
+            if let Some(entry) = tracker.current_time_entry()? {
+                tracker.stop(&entry, None)?;
+            }
+            writeln!(stream, "abandoned pomodoro")?;
+        }
+        _ => writeln!(stream, "unknown command: {}", name)?,
+    }
+    Ok(())
+}
+
 fn handle_connection(mut stream: UnixStream, templates: &Handlebars) -> Result<(), Error> {
+    let command = read_command(&stream)?;
+    if !command.is_empty() && command != "status" {
+        if let Err(e) = handle_command(&mut stream, &command) {
+            writeln!(stream, "error: {}", e)?;
+        }
+        return Ok(());
+    }
+    writeln!(stream, "{}", render_status(templates)?)?;
+    Ok(())
+}
+
+/// Renders the status line with the configured templates.
+fn render_status(templates: &Handlebars) -> Result<String, Error> {
     let config = CONFIG.read().unwrap();
 
     let state = POMODORO_STATE.read().unwrap();
     match state.mode {
-        PomodoroMode::Idle => writeln!(stream, "{}", &config.format.idle)?,
+        PomodoroMode::Idle => Ok(config.format.idle.clone()),
         mode => {
             let mut context = Context {
                 count: state.npomodoros,
                 description: state.description.clone(),
                 project: state.project.clone(),
+                client: state.client.clone(),
+                project_color: state.project_color.clone(),
                 project_or_description: if !state.project.is_empty() {
                     state.project.clone()
                 } else {
@@ -264,6 +1123,18 @@ fn handle_connection(mut stream: UnixStream, templates: &Handlebars) -> Result<(
                 remaining_time: "".to_string(),
                 remaining_time_abs: "".to_string(),
                 task: "".to_string(),
+                snoozed: if state.offsets.snoozed_secs > 0 {
+                    format!("+{}m", state.offsets.snoozed_secs / 60)
+                } else {
+                    "".to_string()
+                },
+                paused: state.offsets.is_paused(),
+                task_done: state.planned_task.map(|x| x.0),
+                task_estimate: state.planned_task.map(|x| x.1),
+                today: state.goals.as_ref().map_or(0, |x| x.today),
+                daily_goal: config.goals.daily_pomodoros.unwrap_or(0),
+                week: state.goals.as_ref().map_or(0, |x| x.week),
+                streak: state.goals.as_ref().map_or(0, |x| x.streak),
             };
 
             let now = Local::now();
@@ -280,7 +1151,7 @@ fn handle_connection(mut stream: UnixStream, templates: &Handlebars) -> Result<(
 
                 context.remaining_time = format!("{:02}:{:02}", mins, secs);
                 context.remaining_time_abs = format!("{:02}:{:02}", mins.abs(), secs);
-                context.task = templates.render(&template, &context)?;
+                context.task = render(templates, &template, &context)?;
             };
 
             let duration = state.finish_time - now;
@@ -296,13 +1167,402 @@ fn handle_connection(mut stream: UnixStream, templates: &Handlebars) -> Result<(
             context.remaining_time = format!("{:02}:{:02}", mins, secs);
             context.remaining_time_abs = format!("{:02}:{:02}", mins.abs(), secs);
 
-            writeln!(stream, "{}", templates.render(&template, &context)?)?;
+            render(templates, &template, &context)
         }
+    }
+}
+
+fn render(templates: &Handlebars, template: &str, context: &Context) -> Result<String, Error> {
+    templates.render(template, context).map_err(|e| {
+        error!("rendering the {} template failed: {}", template, e);
+        e.into()
+    })
+}
+
+fn status() -> Status {
+    let state = POMODORO_STATE.read().unwrap();
+    let running = state.mode != PomodoroMode::Idle;
+    Status {
+        mode: state.mode,
+        count: state.npomodoros,
+        long_break_after: state.long_break_after,
+        description: state.description.clone(),
+        project: state.project.clone(),
+        client: state.client.clone(),
+        phase_start: state.phase.as_ref().map(|x| x.start).filter(|_| running),
+        finish_time: Some(state.finish_time).filter(|_| running),
+        task_finish_time: state.task_finish_time.filter(|_| running),
+        snoozed_secs: state.offsets.snoozed_secs,
+        paused: state.offsets.is_paused(),
+        task_done: state.planned_task.map(|x| x.0),
+        task_estimate: state.planned_task.map(|x| x.1),
+    }
+}
+
+fn today() -> Result<Today, Error> {
+    let config = CONFIG.read().unwrap();
+    let day_start_hour = config.pomodoro.day_start_hour;
+    let now = Local::now();
+    let day = pomodoro::day_of(&now, day_start_hour);
+    let start = pomodoro::start_of_day(day, day_start_hour)
+        .ok_or_else(|| format_err!("invalid date: {}", day))?;
+    let end = pomodoro::start_of_day(day + chrono::Duration::days(1), day_start_hour)
+        .ok_or_else(|| format_err!("invalid date: {}", day))?;
+    Ok(Today {
+        start,
+        end,
+        records: history::load(&config.history, start, end)?,
+        phase: POMODORO_STATE.read().unwrap().phase.clone(),
+    })
+}
+
+/// Pushes the status to event stream subscribers, dropping the ones that
+/// went away.
+fn broadcast(status: &Status) -> Result<(), Error> {
+    let data = serde_json::to_string(status)?;
+    SUBSCRIBERS
+        .lock()
+        .unwrap()
+        .retain(|tx| tx.send(data.clone()).is_ok());
+    Ok(())
+}
+
+/// Compares tokens in constant time.
+fn token_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+/// Accepts the token as a bearer token or, for browsers' `EventSource`
+/// which cannot set headers, as an `access_token` query parameter.
+fn is_authorized(req: &Request, token: &str) -> bool {
+    if let Some(t) = req
+        .header("Authorization")
+        .and_then(|x| x.strip_prefix("Bearer "))
+    {
+        return token_eq(t.trim(), token);
+    }
+    req.split_path()
+        .1
+        .into_iter()
+        .flat_map(|x| x.split('&'))
+        .filter_map(|x| x.strip_prefix("access_token="))
+        .any(|x| token_eq(x, token))
+}
+
+fn stream_events(mut stream: TcpStream) -> Result<(), Error> {
+    let (tx, rx) = mpsc::channel();
+    tx.send(serde_json::to_string(&status())?)?;
+    SUBSCRIBERS.lock().unwrap().push(tx);
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\
+         Cache-Control: no-cache\r\nConnection: keep-alive\r\n\r\n"
+    )?;
+    loop {
+        match rx.recv_timeout(time::Duration::from_secs(KEEPALIVE_SECS)) {
+            Ok(data) => write!(stream, "event: status\ndata: {}\n\n", data)?,
+            Err(RecvTimeoutError::Timeout) => write!(stream, ": keepalive\n\n")?,
+            Err(RecvTimeoutError::Disconnected) => return Ok(()),
+        }
+        stream.flush()?;
+    }
+}
+
+/// Maps a command error to an HTTP status: tracker failures are upstream's
+/// fault, local I/O failures ours, and anything else a bad request.
+fn error_status(e: &Error) -> &'static str {
+    if e.downcast_ref::<TrackerError>().is_some() {
+        "502 Bad Gateway"
+    } else if e.downcast_ref::<io::Error>().is_some()
+        || e.downcast_ref::<serde_json::Error>().is_some()
+    {
+        "500 Internal Server Error"
+    } else {
+        "400 Bad Request"
+    }
+}
+
+fn handle_api(mut stream: TcpStream, templates: &Handlebars, token: &str) -> Result<(), Error> {
+    let req = http::read_request(&stream)?;
+    if !is_authorized(&req, token) {
+        return http::respond(
+            &mut stream,
+            "401 Unauthorized",
+            "text/plain",
+            b"unauthorized\n",
+        );
+    }
+    let (path, query) = req.split_path();
+    let name = path.trim_start_matches('/');
+
+    match (req.method.as_str(), name) {
+        ("GET", "status") => {
+            let json = query.is_some_and(|x| x.split('&').any(|x| x == "format=json"))
+                || req
+                    .header("Accept")
+                    .is_some_and(|x| x.contains("application/json"));
+            if json {
+                let status = status();
+                let mut value = serde_json::to_value(&status)?;
+                value["remaining_secs"] = status
+                    .finish_time
+                    .map(|x| (x - Local::now()).num_seconds())
+                    .into();
+                let body = serde_json::to_vec(&value)?;
+                http::respond(&mut stream, "200 OK", "application/json", &body)
+            } else {
+                let body = render_status(templates)? + "\n";
+                http::respond(&mut stream, "200 OK", "text/plain", body.as_bytes())
+            }
+        }
+        ("GET", "") => http::respond(
+            &mut stream,
+            "200 OK",
+            "text/html; charset=utf-8",
+            DASHBOARD.as_bytes(),
+        ),
+        ("GET", "today") => {
+            let body = serde_json::to_vec(&today()?)?;
+            http::respond(&mut stream, "200 OK", "application/json", &body)
+        }
+        ("GET", "team") => {
+            let body = serde_json::to_vec(&*team::BOARD.read().unwrap())?;
+            http::respond(&mut stream, "200 OK", "application/json", &body)
+        }
+        ("GET", "events") => stream_events(stream),
+        ("POST", name) if API_COMMANDS.contains(&name) => {
+            let command = format!("{} {}", name, req.body.trim());
+            let mut out = Vec::new();
+            match handle_command(&mut out, command.trim()) {
+                Ok(()) => http::respond(&mut stream, "200 OK", "text/plain", &out),
+                Err(e) => {
+                    let body = format!("error: {}\n", e);
+                    http::respond(&mut stream, error_status(&e), "text/plain", body.as_bytes())
+                }
+            }
+        }
+        _ => http::respond(&mut stream, "404 Not Found", "text/plain", b"not found\n"),
+    }
+}
+
+/// Serves the HTTP API on `addr` in the background.
+fn serve_api(addr: &str, token: String, templates: Arc<Handlebars<'static>>) -> Result<(), Error> {
+    let listener = TcpListener::bind(addr)?;
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let templates = templates.clone();
+                    let token = token.clone();
+                    thread::spawn(move || {
+                        if let Err(e) = handle_api(stream, &templates, &token) {
+                            warn!("API request failed: {}", e);
+                        }
+                    });
+                }
+                Err(err) => {
+                    warn!("API accept failed: {}", err);
+                }
+            }
+        }
+    });
+    Ok(())
+}
+
+fn report(matches: &ArgMatches) -> Result<(), Error> {
+    let config = CONFIG.read().unwrap();
+    let tracker = tracker::from_config(&config)?;
+    let day_start_hour = config.pomodoro.day_start_hour;
+    let now = Local::now();
+    let today = pomodoro::day_of(&now, day_start_hour);
+
+    let (from, to) = if let Some(range) = matches.values_of("range") {
+        let dates = range
+            .map(|x| NaiveDate::parse_from_str(x, "%Y-%m-%d"))
+            .collect::<Result<Vec<_>, _>>()?;
+        if dates[0] > dates[1] {
+            return Err(format_err!(
+                "range starts after it ends: {} > {}",
+                dates[0],
+                dates[1]
+            ));
+        }
+        (dates[0], dates[1])
+    } else if matches.is_present("week") {
+        let monday = today - chrono::Duration::days(today.weekday().num_days_from_monday() as i64);
+        (monday, monday + chrono::Duration::days(6))
+    } else {
+        (today, today)
+    };
+    let start = pomodoro::start_of_day(from, day_start_hour)
+        .ok_or_else(|| format_err!("invalid date: {}", from))?;
+    let end = pomodoro::start_of_day(to + chrono::Duration::days(1), day_start_hour)
+        .ok_or_else(|| format_err!("invalid date: {}", to))?;
+
+    let records = history::load(&config.history, start, end)?;
+    let mut report = if matches.is_present("local") {
+        report::from_records(&records)
+    } else {
+        CATALOG.write().unwrap().apply(tracker.fetch_catalog());
+        let entries = tracker.time_entries_between(start, end)?;
+        report::build(
+            &entries,
+            &records,
+            &config.pomodoro,
+            &CATALOG.read().unwrap(),
+            now,
+        )?
     };
+    report.from = Some(from);
+    report.to = Some(to);
 
+    match matches.value_of("format") {
+        Some("json") => println!("{}", serde_json::to_string_pretty(&report)?),
+        Some("csv") => print!("{}", report.to_csv()),
+        _ => print!("{}", report.to_table()),
+    }
+    Ok(())
+}
+
+fn plan(matches: &ArgMatches) -> Result<(), Error> {
+    let config = CONFIG.read().unwrap();
+    let day = match matches.value_of("date") {
+        Some(date) => NaiveDate::parse_from_str(date, "%Y-%m-%d")?,
+        None => pomodoro::day_of(&Local::now(), config.pomodoro.day_start_hour),
+    };
+
+    if let Some(line) = matches.value_of("add") {
+        let task = plan::Task::parse(line)?;
+        plan::add(&config.plan, day, &task)?;
+        return Ok(());
+    }
+
+    let tracker = tracker::from_config(&config)?;
+    CATALOG.write().unwrap().apply(tracker.fetch_catalog());
+    let progress = plan_progress(&config, tracker.as_ref(), day)?;
+    print!("{}", plan::review(&progress, config.pomodoro.pomodoro_min));
     Ok(())
 }
 
+/// Returns the entries of the last day voided by `abandon`.
+fn voided_entries(config: &Config, now: DateTime<Local>) -> Result<HashSet<u64>, Error> {
+    Ok(
+        history::load(&config.history, now - chrono::Duration::days(1), now)?
+            .into_iter()
+            .filter(|x| x.voided)
+            .flat_map(|x| x.all_entry_ids().collect::<Vec<_>>())
+            .collect(),
+    )
+}
+
+/// Status line templates, keyed by the name they are rendered by.
+fn template_sources(format: &FormatConfig) -> [(&'static str, &str); 8] {
+    [
+        ("Work", &format.work),
+        ("Break", &format.r#break),
+        ("overWork", &format.overwork),
+        ("overBreak", &format.overbreak),
+        ("WorkTask", &format.task_work),
+        ("BreakTask", &format.task_break),
+        ("overWorkTask", &format.task_overwork),
+        ("overBreakTask", &format.task_overbreak),
+    ]
+}
+
+/// Describes the phase the daemon would compute from the current entries.
+fn diagnose_state(config: &Config, tracker: &dyn TimeTracker) -> Result<String, Error> {
+    CATALOG.write().unwrap().apply(tracker.fetch_catalog());
+    let entries = tracker.time_entries()?;
+    let voided = voided_entries(config, Local::now())?;
+    let catalog = CATALOG.read().unwrap();
+    let phase = match pomodoro::current_phase(&config.pomodoro, &catalog, &entries, &voided)? {
+        Some(phase) => phase,
+        None => return Ok(format!("Idle, {} recent entries", entries.len())),
+    };
+    Ok(format!(
+        "{:?} {}/{} on \"{}\", {} min planned, ends at {}",
+        phase.mode,
+        phase.count,
+        phase.lengths.long_break_after,
+        entries[0].description,
+        phase.planned_secs / 60,
+        phase.finish_time.format("%H:%M:%S")
+    ))
+}
+
+/// Runs the `doctor` checks and prints the report.  Returns the exit code:
+/// 0 if all passed, 1 if any failed and 2 if the config could not be loaded.
+fn diagnose(config_path: &str, socket: &Path) -> i32 {
+    let mut report = doctor::Report::default();
+    if let Err(e) = Config::load(config_path) {
+        report.add(Verdict::Fail, "config", &format!("{}: {}", config_path, e));
+        print!("{}", report.render());
+        return 2;
+    }
+    report.add(Verdict::Pass, "config", config_path);
+    let config = CONFIG.read().unwrap();
+
+    // The file parsed as a Config above, so it parses as TOML too.
+    let value = fs::read_to_string(config_path)
+        .ok()
+        .and_then(|x| toml::from_str(&x).ok())
+        .unwrap_or_else(|| toml::Value::Table(Default::default()));
+    match doctor::defaulted_keys(&value, &config) {
+        Ok(ref defaulted) if defaulted.is_empty() => (),
+        Ok(defaulted) => report.add(
+            Verdict::Pass,
+            "defaults",
+            &format!("using defaults for {}", defaulted.join(", ")),
+        ),
+        Err(e) => report.add(Verdict::Warn, "defaults", &e.to_string()),
+    }
+
+    for (name, source) in template_sources(&config.format) {
+        let mut t = Handlebars::new();
+        let result = t
+            .register_template_string(name, source)
+            .map_err(Error::from)
+            .and_then(|()| Ok(t.render(name, &Context::sample())?));
+        report.check(&format!("template {}", name), result);
+    }
+
+    if let TrackerConfig::Toggl = config.tracker {
+        let result = Toggl::new(config.toggl_token.clone())
+            .me()
+            .map(|x| format!("{} <{}>", x.fullname, x.email));
+        report.check("toggl token", result);
+    }
+    match tracker::from_config(&config) {
+        Ok(tracker) => report.check("state", diagnose_state(&config, tracker.as_ref())),
+        Err(e) => report.add(Verdict::Fail, "tracker", &e.to_string()),
+    }
+
+    report.check(
+        "socket",
+        client::request(socket, "status").map(|x| format!("{}: {}", socket.display(), x)),
+    );
+
+    match notifiers(&config) {
+        Ok(ref notifiers) if notifiers.is_empty() => {
+            report.add(Verdict::Warn, "notifiers", "none configured")
+        }
+        Ok(notifiers) => {
+            for n in notifiers {
+                let result = n.check().map(|()| "ready (dry run)".to_string());
+                report.check(&format!("notifier {}", n.backend()), result);
+            }
+        }
+        Err(e) => report.add(Verdict::Fail, "notifiers", &e.to_string()),
+    }
+
+    print!("{}", report.render());
+    report.exit_code()
+}
+
 fn main() -> Result<(), Error> {
     let matches = App::new("toggdoro")
         .version("0.1")
@@ -324,6 +1584,66 @@ fn main() -> Result<(), Error> {
                 .help("Sets UNIX domain socket path")
                 .takes_value(true),
         )
+        .subcommand(
+            SubCommand::with_name("report")
+                .about("Reports pomodoro statistics")
+                .arg(
+                    Arg::with_name("day")
+                        .long("day")
+                        .help("Reports today (default)"),
+                )
+                .arg(
+                    Arg::with_name("week")
+                        .long("week")
+                        .help("Reports this week")
+                        .conflicts_with("day"),
+                )
+                .arg(
+                    Arg::with_name("range")
+                        .long("range")
+                        .value_names(&["FROM", "TO"])
+                        .help("Reports the given dates (YYYY-MM-DD)")
+                        .number_of_values(2)
+                        .conflicts_with_all(&["day", "week"]),
+                )
+                .arg(
+                    Arg::with_name("local")
+                        .long("local")
+                        .help("Reports from the local history instead of the tracker"),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .short("f")
+                        .long("format")
+                        .value_name("FORMAT")
+                        .help("Sets output format")
+                        .possible_values(&["table", "json", "csv"])
+                        .default_value("table"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("plan")
+                .about("Compares the day's plan with actuals")
+                .arg(
+                    Arg::with_name("date")
+                        .long("date")
+                        .value_name("DATE")
+                        .help("Sets the day (YYYY-MM-DD), today by default")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("add")
+                        .long("add")
+                        .value_name("TASK")
+                        .help("Adds a task: <estimate> <description> [@project] [#tag...]")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(SubCommand::with_name("team").about("Shows the team status board"))
+        .subcommand(SubCommand::with_name("doctor").about("Diagnoses the configuration and daemon"))
+        .subcommand(
+            SubCommand::with_name("tui").about("Shows a terminal dashboard of the running daemon"),
+        )
         .get_matches();
 
     let home = env::var("HOME").unwrap_or(".".to_string());
@@ -332,50 +1652,330 @@ fn main() -> Result<(), Error> {
         .map(|x| x.to_string())
         .unwrap_or(home.to_string() + "/.config/toggdoro/config.toml");
 
-    Config::load(&config_path)?;
-
     let path = env::var("XDG_RUNTIME_DIR")
         .map(|x| x.to_string() + "/toggdoro.sock")
         .unwrap_or(home.to_string() + "/.toggdoro.sock");
 
-    let listener = UnixListener::bind(&path)?;
+    if matches.subcommand_matches("doctor").is_some() {
+        process::exit(diagnose(&config_path, Path::new(&path)));
+    }
+
+    Config::load(&config_path)?;
+    logging::init(&CONFIG.read().unwrap().log)?;
+
+    if let Some(matches) = matches.subcommand_matches("report") {
+        return report(matches);
+    }
+    if let Some(matches) = matches.subcommand_matches("plan") {
+        return plan(matches);
+    }
+    if matches.subcommand_matches("team").is_some() {
+        let config = CONFIG.read().unwrap();
+        let members = Team::new(&config.team).refresh(&config);
+        print!("{}", team::board(&members, Local::now()));
+        return Ok(());
+    }
+    if matches.subcommand_matches("tui").is_some() {
+        #[cfg(feature = "tui")]
+        return tui::run(Path::new(&path));
+        #[cfg(not(feature = "tui"))]
+        return Err(format_err!("toggdoro was built without the tui feature"));
+    }
+
+    let (listener, activated) = match systemd::listen_fds()? {
+        Some(listener) => (listener, true),
+        None => (UnixListener::bind(&path)?, false),
+    };
 
+    // The accept loop only notices the shutdown flag on its next
+    // connection, so the signal handler makes one.
     let signals = Signals::new([SIGTERM, SIGINT])?;
+    let wake = listener
+        .local_addr()?
+        .as_pathname()
+        .map(|x| x.to_path_buf());
     thread::spawn(move || {
-        for _sig in signals.forever() {
-            fs::remove_file(&path).unwrap();
-            process::exit(130);
+        if signals.forever().next().is_some() {
+            SHUTDOWN.store(true, Ordering::SeqCst);
+            if let Some(ref wake) = wake {
+                let _ = UnixStream::connect(wake);
+            }
         }
     });
 
-    thread::spawn(monitor);
+    {
+        let config = CONFIG.read().unwrap();
+        let mut state = POMODORO_STATE.write().unwrap();
+        let now = Local::now();
+        state.offsets = Offsets::load().unwrap_or_else(|e| {
+            warn!("ignoring saved offsets: {}", e);
+            Offsets::default()
+        });
+        // A phase saved more than a day ago is long over and not resumed.
+        state.phase = history::load_running()
+            .unwrap_or_else(|e| {
+                warn!("ignoring the saved phase: {}", e);
+                None
+            })
+            .filter(|x| now - x.start < chrono::Duration::days(1));
+        state.voided = voided_entries(&config, now)?;
+
+        let today = pomodoro::day_of(&now, config.pomodoro.day_start_hour);
+        let start = pomodoro::start_of_day(today, config.pomodoro.day_start_hour)
+            .ok_or_else(|| format_err!("invalid date: {}", today))?;
+        let mut metrics = METRICS.write().unwrap();
+        for record in history::load(&config.history, start, now)? {
+            if record.is_pomodoro() {
+                metrics.add_pomodoro(today, record.project.as_deref().unwrap_or(NO_PROJECT));
+            }
+        }
+        if let Some(ref addr) = config.metrics.listen {
+            metrics::serve(addr)?;
+        }
+    }
+    let tracker = tracker::from_config(&CONFIG.read().unwrap())?;
+    thread::spawn(move || monitor(tracker));
+    if let Some(interval) = systemd::watchdog_interval() {
+        thread::spawn(move || watchdog(interval));
+    }
+    if !CONFIG.read().unwrap().team.members.is_empty() {
+        let slack = match CONFIG.read().unwrap().team.slack {
+            Some(ref url) => Some(
+                SlackNotifier::new(url)
+                    .map_err(|e| format_err!("invalid team.slack URL: {}", e))?,
+            ),
+            None => None,
+        };
+        thread::spawn(move || team_monitor(slack));
+    }
+    if CONFIG.read().unwrap().sync.leader.is_some() {
+        let tracker = tracker::from_config(&CONFIG.read().unwrap())?;
+        thread::spawn(move || sync_monitor(tracker));
+    }
 
     let templates = Arc::new({
         let mut t = Handlebars::new();
         let config = CONFIG.read().unwrap();
 
-        t.register_template_string("Work", &config.format.work)?;
-        t.register_template_string("Break", &config.format.r#break)?;
-        t.register_template_string("overWork", &config.format.overwork)?;
-        t.register_template_string("overBreak", &config.format.overbreak)?;
-        t.register_template_string("WorkTask", &config.format.task_work)?;
-        t.register_template_string("BreakTask", &config.format.task_break)?;
-        t.register_template_string("overWorkTask", &config.format.task_overwork)?;
-        t.register_template_string("overBreakTask", &config.format.task_overbreak)?;
+        for (name, source) in template_sources(&config.format) {
+            t.register_template_string(name, source)?;
+        }
         t
     });
 
+    {
+        let config = CONFIG.read().unwrap();
+        if let Some(ref addr) = config.api.listen {
+            // Config::validate ensures a non-empty token.
+            let token = config.api.token.clone().unwrap_or_default();
+            serve_api(addr, token, templates.clone())?;
+        }
+    }
+
+    systemd::notify("READY=1")?;
+
     for stream in listener.incoming() {
+        if SHUTDOWN.load(Ordering::SeqCst) {
+            break;
+        }
         let templates = templates.clone();
         match stream {
             Ok(stream) => {
                 thread::spawn(move || handle_connection(stream, &templates));
             }
             Err(err) => {
-                println!("accept failed: {:?}", err);
+                warn!("accept failed: {}", err);
             }
         }
     }
 
+    systemd::notify("STOPPING=1")?;
+    if !activated {
+        fs::remove_file(&path)?;
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use toggdoro::catalog::Catalog;
+
+    /// Tests share the daemon state, so they take turns.
+    static SERIAL: Mutex<()> = Mutex::new(());
+
+    /// Keeps entries in memory, newest first.
+    struct FakeTracker {
+        entries: Mutex<Vec<TimeEntry>>,
+    }
+
+    impl TimeTracker for FakeTracker {
+        fn time_entries(&self) -> Result<Vec<TimeEntry>, Error> {
+            Ok(self.entries.lock().unwrap().clone())
+        }
+
+        fn time_entries_between(
+            &self,
+            start: DateTime<Local>,
+            end: DateTime<Local>,
+        ) -> Result<Vec<TimeEntry>, Error> {
+            let mut entries = self.time_entries()?;
+            entries.retain(|x| x.start >= start && x.start < end);
+            Ok(entries)
+        }
+
+        fn current_time_entry(&self) -> Result<Option<TimeEntry>, Error> {
+            Ok(self.time_entries()?.into_iter().find(|x| x.duration < 0))
+        }
+
+        fn start(
+            &self,
+            _catalog: &Catalog,
+            description: &str,
+            project: Option<&str>,
+            tags: &[String],
+        ) -> Result<TimeEntry, Error> {
+            let mut entries = self.entries.lock().unwrap();
+            let id = entries.iter().map(|x| x.id).max().unwrap_or(0) + 1;
+            let entry = TimeEntry::new(id, description, project, tags, Local::now());
+            entries.insert(0, entry.clone());
+            Ok(entry)
+        }
+
+        fn stop(&self, entry: &TimeEntry, at: Option<DateTime<Local>>) -> Result<TimeEntry, Error> {
+            let mut entries = self.entries.lock().unwrap();
+            let x = entries
+                .iter_mut()
+                .find(|x| x.id == entry.id)
+                .ok_or_else(|| format_err!("no such entry: {}", entry.id))?;
+            x.stop_at(at.unwrap_or_else(Local::now));
+            Ok(x.clone())
+        }
+    }
+
+    struct Recorder(Rc<RefCell<Vec<String>>>);
+
+    impl Notifier for Recorder {
+        fn notify(&self, mode: PomodoroMode, min: u32) -> Result<(), Error> {
+            self.0.borrow_mut().push(format!("{:?} {}", mode, min));
+            Ok(())
+        }
+
+        fn message(&self, text: &str) -> Result<(), Error> {
+            self.0.borrow_mut().push(text.to_string());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn update_follows_the_cycle_offline() {
+        let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        let dir = env::temp_dir().join(format!("toggdoro-update-{}", process::id()));
+        env::set_var("XDG_DATA_HOME", &dir);
+        *CONFIG.write().unwrap() = toml::from_str("version = 1").unwrap();
+        *POMODORO_STATE.write().unwrap() = Default::default();
+
+        let now = Local::now();
+        let minutes = chrono::Duration::minutes;
+        let mut first = TimeEntry::new(1, "write", None, &[], now - minutes(58));
+        first.stop_at(now - minutes(33));
+        let mut pause = TimeEntry::new(2, BREAK_DESCRIPTION, None, &[], now - minutes(33));
+        pause.stop_at(now - minutes(28));
+        let running = TimeEntry::new(3, "write", None, &[], now - minutes(28));
+        let tracker = FakeTracker {
+            entries: Mutex::new(vec![running, pause, first]),
+        };
+        let sent = Rc::new(RefCell::new(Vec::new()));
+        let notifiers: Vec<Box<dyn Notifier>> = vec![Box::new(Recorder(sent.clone()))];
+
+        update(&tracker, &notifiers).unwrap();
+        {
+            let state = POMODORO_STATE.read().unwrap();
+            assert_eq!(state.mode, PomodoroMode::Work);
+            assert_eq!(state.npomodoros, 2);
+            assert_eq!(state.finish_time, now - minutes(3));
+        }
+        assert_eq!(*sent.borrow(), vec!["Break 5"]);
+
+        // The reminder is not repeated within five minutes.
+        update(&tracker, &notifiers).unwrap();
+        assert_eq!(sent.borrow().len(), 1);
+
+        start_break(&CONFIG.read().unwrap().pomodoro, &tracker).unwrap();
+        update(&tracker, &notifiers).unwrap();
+        {
+            let state = POMODORO_STATE.read().unwrap();
+            assert_eq!(state.mode, PomodoroMode::Break);
+            assert_eq!(state.npomodoros, 2);
+            assert_eq!(state.description, BREAK_DESCRIPTION);
+        }
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    fn leader_status(mode: PomodoroMode, description: &str) -> Status {
+        Status {
+            mode,
+            count: 1,
+            long_break_after: 4,
+            description: description.to_string(),
+            project: String::new(),
+            client: String::new(),
+            phase_start: Some(Local::now()),
+            finish_time: None,
+            task_finish_time: None,
+            snoozed_secs: 0,
+            paused: false,
+            task_done: None,
+            task_estimate: None,
+        }
+    }
+
+    #[test]
+    fn follow_leader_mirrors_the_event_stream() {
+        let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        let work = leader_status(PomodoroMode::Work, "write");
+        let rest = leader_status(PomodoroMode::Break, BREAK_DESCRIPTION);
+        let events = format!(
+            ": keepalive\n\nevent: status\ndata: {}\n\nevent: status\ndata: {}\n\n",
+            serde_json::to_string(&work).unwrap(),
+            serde_json::to_string(&rest).unwrap()
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let leader = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let req = http::read_request(&stream).unwrap();
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\r\n{}",
+                events
+            )
+            .unwrap();
+            req
+        });
+
+        let config: Config = toml::from_str(&format!(
+            "version = 1\n[sync]\nleader = \"{}\"\ntoken = \"s3cret\"\n",
+            url
+        ))
+        .unwrap();
+        let tracker = FakeTracker {
+            entries: Mutex::new(Vec::new()),
+        };
+        *POMODORO_STATE.write().unwrap() = Default::default();
+        follow_leader(&config, &tracker, &format!("{}/", url)).unwrap();
+
+        let req = leader.join().unwrap();
+        assert_eq!(req.path, "/events");
+        assert_eq!(req.header("Authorization"), Some("Bearer s3cret"));
+        let entries = tracker.time_entries().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].description, BREAK_DESCRIPTION);
+        assert!(entries[0].duration < 0);
+        assert_eq!(entries[1].description, "write");
+        assert!(entries[1].duration >= 0);
+        assert_eq!(POMODORO_STATE.read().unwrap().leader, Some(rest));
+    }
+}
@@ -0,0 +1,433 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::RwLock;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local, NaiveDate};
+use failure::Error;
+use lazy_static::lazy_static;
+use log::{info, warn};
+
+use crate::catalog::{Catalog, CatalogUpdate};
+use crate::http;
+use crate::notifier::Notifier;
+use crate::pomodoro::PomodoroMode;
+use crate::toggl::TimeEntry;
+use crate::tracker::{TimeTracker, TrackerError};
+
+/// Upper bounds in seconds of the request latency histogram buckets.
+const LATENCY_BUCKETS: [f64; 8] = [0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+#[derive(Default)]
+struct Histogram {
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, secs: f64) {
+        for (bucket, le) in self.buckets.iter_mut().zip(LATENCY_BUCKETS.iter()) {
+            if secs <= *le {
+                *bucket += 1;
+            }
+        }
+        self.count += 1;
+        self.sum += secs;
+    }
+}
+
+/// Values exported on `/metrics`, updated by the monitor loop.
+pub struct Metrics {
+    mode: PomodoroMode,
+    remaining_secs: i64,
+    day: Option<NaiveDate>,
+    pomodoros_today: BTreeMap<String, u32>,
+    /// Keyed by notifier backend and result.
+    notifications: BTreeMap<(&'static str, &'static str), u64>,
+    /// Keyed by tracker operation.
+    requests: BTreeMap<&'static str, Histogram>,
+    request_errors: BTreeMap<&'static str, u64>,
+    last_sync: Option<DateTime<Local>>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            mode: PomodoroMode::Idle,
+            remaining_secs: 0,
+            day: None,
+            pomodoros_today: BTreeMap::new(),
+            notifications: BTreeMap::new(),
+            requests: BTreeMap::new(),
+            request_errors: BTreeMap::new(),
+            last_sync: None,
+        }
+    }
+}
+
+lazy_static! {
+    pub static ref METRICS: RwLock<Metrics> = RwLock::new(Default::default());
+}
+
+impl Metrics {
+    fn set_day(&mut self, day: NaiveDate) {
+        if self.day != Some(day) {
+            self.day = Some(day);
+            self.pomodoros_today.clear();
+        }
+    }
+
+    pub fn set_phase(&mut self, mode: PomodoroMode, remaining_secs: i64, day: NaiveDate) {
+        self.mode = mode;
+        self.remaining_secs = remaining_secs;
+        self.set_day(day);
+    }
+
+    pub fn add_pomodoro(&mut self, day: NaiveDate, project: &str) {
+        self.set_day(day);
+        *self.pomodoros_today.entry(project.to_string()).or_default() += 1;
+    }
+
+    fn add_notification(&mut self, backend: &'static str, ok: bool) {
+        let result = if ok { "ok" } else { "error" };
+        *self.notifications.entry((backend, result)).or_default() += 1;
+    }
+
+    fn add_request(&mut self, op: &'static str, elapsed: Duration, ok: bool) {
+        self.requests
+            .entry(op)
+            .or_default()
+            .observe(elapsed.as_secs_f64());
+        if ok {
+            self.last_sync = Some(Local::now());
+        } else {
+            *self.request_errors.entry(op).or_default() += 1;
+        }
+    }
+
+    /// Renders the metrics in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let mut s = String::new();
+
+        header(&mut s, "toggdoro_mode", "gauge", "Current pomodoro mode.");
+        for mode in &[PomodoroMode::Idle, PomodoroMode::Work, PomodoroMode::Break] {
+            let _ = writeln!(
+                s,
+                "toggdoro_mode{{mode=\"{}\"}} {}",
+                format!("{:?}", mode).to_lowercase(),
+                (*mode == self.mode) as u8
+            );
+        }
+
+        header(
+            &mut s,
+            "toggdoro_remaining_seconds",
+            "gauge",
+            "Seconds left in the current phase, negative when over.",
+        );
+        let _ = writeln!(s, "toggdoro_remaining_seconds {}", self.remaining_secs);
+
+        header(
+            &mut s,
+            "toggdoro_pomodoros_today",
+            "gauge",
+            "Pomodoros completed today.",
+        );
+        for (project, n) in &self.pomodoros_today {
+            let _ = writeln!(
+                s,
+                "toggdoro_pomodoros_today{{project=\"{}\"}} {}",
+                escape(project),
+                n
+            );
+        }
+
+        header(
+            &mut s,
+            "toggdoro_notifications_total",
+            "counter",
+            "Notifications sent.",
+        );
+        for ((backend, result), n) in &self.notifications {
+            let _ = writeln!(
+                s,
+                "toggdoro_notifications_total{{backend=\"{}\",result=\"{}\"}} {}",
+                backend, result, n
+            );
+        }
+
+        header(
+            &mut s,
+            "toggdoro_api_request_duration_seconds",
+            "histogram",
+            "Latency of time tracker API requests.",
+        );
+        for (op, h) in &self.requests {
+            for (le, n) in LATENCY_BUCKETS.iter().zip(h.buckets.iter()) {
+                let _ = writeln!(
+                    s,
+                    "toggdoro_api_request_duration_seconds_bucket{{op=\"{}\",le=\"{}\"}} {}",
+                    op, le, n
+                );
+            }
+            let _ = writeln!(
+                s,
+                "toggdoro_api_request_duration_seconds_bucket{{op=\"{}\",le=\"+Inf\"}} {}",
+                op, h.count
+            );
+            let _ = writeln!(
+                s,
+                "toggdoro_api_request_duration_seconds_sum{{op=\"{}\"}} {}",
+                op, h.sum
+            );
+            let _ = writeln!(
+                s,
+                "toggdoro_api_request_duration_seconds_count{{op=\"{}\"}} {}",
+                op, h.count
+            );
+        }
+
+        header(
+            &mut s,
+            "toggdoro_api_request_errors_total",
+            "counter",
+            "Failed time tracker API requests.",
+        );
+        for (op, n) in &self.request_errors {
+            let _ = writeln!(
+                s,
+                "toggdoro_api_request_errors_total{{op=\"{}\"}} {}",
+                op, n
+            );
+        }
+
+        if let Some(last_sync) = self.last_sync {
+            header(
+                &mut s,
+                "toggdoro_last_sync_timestamp_seconds",
+                "gauge",
+                "Time of the last successful time tracker request.",
+            );
+            let _ = writeln!(
+                s,
+                "toggdoro_last_sync_timestamp_seconds {}",
+                last_sync.timestamp()
+            );
+        }
+        s
+    }
+}
+
+fn header(s: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(s, "# HELP {} {}", name, help);
+    let _ = writeln!(s, "# TYPE {} {}", name, kind);
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Runs a time tracker request, recording its latency and result.  Errors
+/// come back as `TrackerError`.
+pub fn timed<T, F>(op: &'static str, f: F) -> Result<T, Error>
+where
+    F: FnOnce() -> Result<T, Error>,
+{
+    let start = Instant::now();
+    let result = f();
+    METRICS
+        .write()
+        .unwrap()
+        .add_request(op, start.elapsed(), result.is_ok());
+    result.map_err(|e| TrackerError(e).into())
+}
+
+/// A notifier that counts the notifications it sends.
+pub struct Instrumented {
+    backend: &'static str,
+    inner: Box<dyn Notifier>,
+}
+
+impl Instrumented {
+    pub fn new(backend: &'static str, inner: Box<dyn Notifier>) -> Self {
+        Instrumented { backend, inner }
+    }
+
+    pub fn backend(&self) -> &'static str {
+        self.backend
+    }
+
+    fn record(&self, what: &str, result: Result<(), Error>) -> Result<(), Error> {
+        match result {
+            Ok(()) => info!("{} notification sent: {}", self.backend, what),
+            Err(ref e) => warn!("{} notification failed: {}: {}", self.backend, what, e),
+        }
+        METRICS
+            .write()
+            .unwrap()
+            .add_notification(self.backend, result.is_ok());
+        result
+    }
+}
+
+impl Notifier for Instrumented {
+    fn notify(&self, mode: PomodoroMode, min: u32) -> Result<(), Error> {
+        self.record(
+            &format!("{:?} {} min", mode, min),
+            self.inner.notify(mode, min),
+        )
+    }
+
+    fn message(&self, text: &str) -> Result<(), Error> {
+        self.record(text, self.inner.message(text))
+    }
+
+    fn check(&self) -> Result<(), Error> {
+        self.inner.check()
+    }
+}
+
+/// A time tracker that records the latency and result of its requests.
+pub struct InstrumentedTracker {
+    inner: Box<dyn TimeTracker>,
+}
+
+impl InstrumentedTracker {
+    pub fn new(inner: Box<dyn TimeTracker>) -> Self {
+        InstrumentedTracker { inner }
+    }
+}
+
+impl TimeTracker for InstrumentedTracker {
+    fn time_entries(&self) -> Result<Vec<TimeEntry>, Error> {
+        timed("time_entries", || self.inner.time_entries())
+    }
+
+    fn time_entries_between(
+        &self,
+        start: DateTime<Local>,
+        end: DateTime<Local>,
+    ) -> Result<Vec<TimeEntry>, Error> {
+        timed("time_entries_between", || {
+            self.inner.time_entries_between(start, end)
+        })
+    }
+
+    fn current_time_entry(&self) -> Result<Option<TimeEntry>, Error> {
+        timed("current_time_entry", || self.inner.current_time_entry())
+    }
+
+    fn start(
+        &self,
+        catalog: &Catalog,
+        description: &str,
+        project: Option<&str>,
+        tags: &[String],
+    ) -> Result<TimeEntry, Error> {
+        timed("start", || {
+            self.inner.start(catalog, description, project, tags)
+        })
+    }
+
+    fn stop(&self, entry: &TimeEntry, at: Option<DateTime<Local>>) -> Result<TimeEntry, Error> {
+        timed("stop", || self.inner.stop(entry, at))
+    }
+
+    fn fetch_catalog(&self) -> CatalogUpdate {
+        let start = Instant::now();
+        let update = self.inner.fetch_catalog();
+        METRICS
+            .write()
+            .unwrap()
+            .add_request("catalog", start.elapsed(), !update.failed);
+        update
+    }
+}
+
+fn handle_scrape(mut stream: TcpStream) -> Result<(), Error> {
+    let req = http::read_request(&stream)?;
+    if req.method == "GET" && req.path == "/metrics" {
+        let body = METRICS.read().unwrap().render();
+        http::respond(
+            &mut stream,
+            "200 OK",
+            "text/plain; version=0.0.4",
+            body.as_bytes(),
+        )
+    } else {
+        http::respond(&mut stream, "404 Not Found", "text/plain", b"not found\n")
+    }
+}
+
+/// Serves `/metrics` on `addr` in the background, one thread per
+/// connection so a stalled client cannot hold up the others.  Returns the
+/// bound address.
+pub fn serve(addr: &str) -> Result<SocketAddr, Error> {
+    let listener = TcpListener::bind(addr)?;
+    let local_addr = listener.local_addr()?;
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    thread::spawn(move || {
+                        if let Err(e) = handle_scrape(stream) {
+                            warn!("metrics request failed: {}", e);
+                        }
+                    });
+                }
+                Err(err) => {
+                    warn!("metrics accept failed: {}", err);
+                }
+            }
+        }
+    });
+    Ok(local_addr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tracker::local::LocalTracker;
+    use std::io::Read;
+    use std::{env, fs, process};
+
+    fn scrape(url: &str) -> (u16, String) {
+        let mut res = reqwest::get(url).unwrap();
+        let mut body = String::new();
+        res.read_to_string(&mut body).unwrap();
+        (res.status().as_u16(), body)
+    }
+
+    #[test]
+    fn scrapes_tracker_requests() {
+        let path = env::temp_dir().join(format!("toggdoro-metrics-{}.json", process::id()));
+        let tracker = InstrumentedTracker::new(Box::new(LocalTracker::new(path.to_str())));
+        let entry = tracker
+            .start(&Catalog::default(), "write", None, &[])
+            .unwrap();
+        tracker.stop(&entry, None).unwrap();
+        assert!(tracker
+            .stop(&TimeEntry::new(0, "gone", None, &[], Local::now()), None)
+            .is_err());
+        let _ = fs::remove_file(path);
+
+        let addr = serve("127.0.0.1:0").unwrap();
+        // A client that never sends its request does not block the scrape.
+        let _idle = TcpStream::connect(addr).unwrap();
+        let (status, body) = scrape(&format!("http://{}/metrics", addr));
+        assert_eq!(status, 200);
+        assert!(body.contains("toggdoro_api_request_duration_seconds_count{op=\"start\"} 1"));
+        assert!(body.contains("toggdoro_api_request_duration_seconds_count{op=\"stop\"} 2"));
+        assert!(body.contains("toggdoro_api_request_errors_total{op=\"stop\"} 1"));
+        assert!(body.contains("toggdoro_last_sync_timestamp_seconds "));
+
+        let (status, _) = scrape(&format!("http://{}/other", addr));
+        assert_eq!(status, 404);
+    }
+}
@@ -8,4 +8,11 @@ pub mod slack;
 
 pub trait Notifier {
     fn notify(&self, mode: PomodoroMode, min: u32) -> Result<(), Error>;
+
+    fn message(&self, text: &str) -> Result<(), Error>;
+
+    /// Checks that notifications could be delivered, without sending one.
+    fn check(&self) -> Result<(), Error> {
+        Ok(())
+    }
 }
@@ -20,4 +20,18 @@ impl Notifier for DBusNotifier {
             .map_err(|e| format_err!("{}", e))?;
         Ok(())
     }
+
+    fn message(&self, text: &str) -> Result<(), Error> {
+        notify_rust::Notification::new()
+            .summary("Toggdoro")
+            .body(text)
+            .show()
+            .map_err(|e| format_err!("{}", e))?;
+        Ok(())
+    }
+
+    fn check(&self) -> Result<(), Error> {
+        notify_rust::get_server_information().map_err(|e| format_err!("{}", e))?;
+        Ok(())
+    }
 }
@@ -1,10 +1,15 @@
-use failure::Error;
+use std::path::Path;
+
+use failure::{format_err, Error};
 use lettre::{SendmailTransport, Transport};
 use lettre_email::Email;
 
 use crate::notifier::Notifier;
 use crate::pomodoro::PomodoroMode;
 
+/// The command `SendmailTransport::new` delivers with.
+const SENDMAIL: &str = "/usr/sbin/sendmail";
+
 pub struct MailNotifier {
     from: String,
     to: String,
@@ -32,4 +37,30 @@ impl Notifier for MailNotifier {
         mailer.send(email.into())?;
         Ok(())
     }
+
+    fn message(&self, text: &str) -> Result<(), Error> {
+        let email = Email::builder()
+            .from(&self.from as &str)
+            .to(&self.to as &str)
+            .subject(text)
+            .text("")
+            .build()?;
+
+        let mut mailer = SendmailTransport::new();
+        mailer.send(email.into())?;
+        Ok(())
+    }
+
+    fn check(&self) -> Result<(), Error> {
+        Email::builder()
+            .from(&self.from as &str)
+            .to(&self.to as &str)
+            .subject("check")
+            .text("")
+            .build()?;
+        if !Path::new(SENDMAIL).exists() {
+            return Err(format_err!("{} not found", SENDMAIL));
+        }
+        Ok(())
+    }
 }
@@ -1,17 +1,28 @@
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
 use failure::{format_err, Error};
+use reqwest::Url;
 use slack_hook::{PayloadBuilder, Slack};
 
 use crate::notifier::Notifier;
 use crate::pomodoro::PomodoroMode;
 
+/// How long `check` waits to connect to the webhook host.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
 pub struct SlackNotifier {
     slack: Slack,
+    url: Url,
 }
 
 impl SlackNotifier {
     pub fn new(url: &str) -> Result<Self, Error> {
         let slack = Slack::new(url as &str).map_err(|e| format_err!("{}", e))?;
-        Ok(SlackNotifier { slack })
+        Ok(SlackNotifier {
+            slack,
+            url: Url::parse(url)?,
+        })
     }
 }
 
@@ -32,4 +43,48 @@ impl Notifier for SlackNotifier {
         self.slack.send(&p).map_err(|e| format_err!("{}", e))?;
         Ok(())
     }
+
+    fn message(&self, text: &str) -> Result<(), Error> {
+        let p = PayloadBuilder::new()
+            .username("toggdoro")
+            .icon_emoji(":tomato:")
+            .text(text)
+            .build()
+            .map_err(|e| format_err!("{}", e))?;
+
+        self.slack.send(&p).map_err(|e| format_err!("{}", e))?;
+        Ok(())
+    }
+
+    /// Connects to the webhook host; posting would show up in the channel.
+    fn check(&self) -> Result<(), Error> {
+        let host = self
+            .url
+            .host_str()
+            .ok_or_else(|| format_err!("no host in {}", self.url))?;
+        let port = self.url.port_or_known_default().unwrap_or(443);
+        let addr = (host, port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| format_err!("cannot resolve {}", host))?;
+        TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)
+            .map_err(|e| format_err!("cannot connect to {}:{}: {}", host, port, e))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn check_connects_to_the_webhook_host() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/services/T0/B0/x", listener.local_addr().unwrap());
+        assert!(SlackNotifier::new(&url).unwrap().check().is_ok());
+        drop(listener);
+        assert!(SlackNotifier::new(&url).unwrap().check().is_err());
+        assert!(SlackNotifier::new("not a url").is_err());
+    }
 }
@@ -0,0 +1,389 @@
+use std::fmt::Write as _;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+use chrono::{DateTime, Duration, Local, NaiveDate};
+use failure::{format_err, Error};
+use serde_derive::Serialize;
+
+use crate::budget;
+use crate::catalog::Catalog;
+use crate::config::{self, PlanConfig};
+use crate::duration::hm;
+use crate::history::Record;
+use crate::pomodoro;
+use crate::toggl::TimeEntry;
+
+/// A planned task, written in the plan file as
+/// `<estimate> <description> [@<project>] [#<tag>...]`.
+#[derive(Clone, Debug, Serialize)]
+pub struct Task {
+    pub estimate: u32,
+    pub description: String,
+    pub project: Option<String>,
+    pub tags: Vec<String>,
+}
+
+/// Parses `<description> [@<project>] [#<tag>...]`.  A project name runs
+/// until the next tag, so it may contain spaces.
+pub fn parse_task_args(args: &str) -> (String, Option<String>, Vec<String>) {
+    let mut description = Vec::new();
+    let mut project: Option<Vec<&str>> = None;
+    let mut tags = Vec::new();
+
+    for word in args.split_whitespace() {
+        if let Some(tag) = word.strip_prefix('#') {
+            tags.push(tag.to_string());
+        } else if let Some(name) = word.strip_prefix('@') {
+            project = Some(vec![name]);
+        } else if let Some(ref mut p) = project {
+            p.push(word);
+        } else {
+            description.push(word);
+        }
+    }
+    (description.join(" "), project.map(|x| x.join(" ")), tags)
+}
+
+impl Task {
+    pub fn parse(line: &str) -> Result<Self, Error> {
+        let line = line.trim();
+        let (estimate, rest) = match line.find(char::is_whitespace) {
+            Some(i) => (&line[..i], &line[i..]),
+            None => (line, ""),
+        };
+        let estimate = estimate
+            .parse()
+            .map_err(|_| format_err!("invalid estimate: {}", line))?;
+        let (description, project, tags) = parse_task_args(rest);
+        Ok(Task {
+            estimate,
+            description,
+            project,
+            tags,
+        })
+    }
+
+    pub fn to_line(&self) -> String {
+        let mut s = format!("{} {}", self.estimate, self.description);
+        if let Some(ref project) = self.project {
+            let _ = write!(s, " @{}", project);
+        }
+        for tag in &self.tags {
+            let _ = write!(s, " #{}", tag);
+        }
+        s
+    }
+
+    fn is_task(&self, description: &str, project: Option<&str>) -> bool {
+        self.description == description
+            && self
+                .project
+                .as_ref()
+                .is_none_or(|x| project.is_some_and(|p| p.eq_ignore_ascii_case(x)))
+    }
+
+    /// Returns true if the entry works on this task: same description and,
+    /// if the task names one, same project.
+    pub fn matches(&self, entry: &TimeEntry, catalog: &Catalog) -> bool {
+        self.is_task(&entry.description, catalog.project_name(entry).as_deref())
+    }
+
+    pub fn matches_record(&self, record: &Record) -> bool {
+        self.is_task(&record.description, record.project.as_deref())
+    }
+}
+
+pub fn path(config: &PlanConfig, day: NaiveDate) -> PathBuf {
+    let dir = match config.dir {
+        Some(ref dir) => PathBuf::from(dir),
+        None => config::data_dir().join("plans"),
+    };
+    dir.join(day.format("%Y-%m-%d.txt").to_string())
+}
+
+/// Loads the plan of the day.  A missing file is an empty plan.
+pub fn load(config: &PlanConfig, day: NaiveDate) -> Result<Vec<Task>, Error> {
+    let contents = match fs::read_to_string(path(config, day)) {
+        Ok(contents) => contents,
+        Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    contents
+        .lines()
+        .filter(|x| !x.trim().is_empty())
+        .map(Task::parse)
+        .collect()
+}
+
+pub fn add(config: &PlanConfig, day: NaiveDate, task: &Task) -> Result<(), Error> {
+    let path = path(config, day);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", task.to_line())?;
+    Ok(())
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct TaskProgress {
+    pub task: Task,
+    /// Pomodoros completed on the task.
+    pub done: u32,
+    pub spent_secs: i64,
+}
+
+/// Matches the day's entries and history records against the plan.  Time
+/// spent within the day is summed over the entries of the same task as the
+/// latest one matching, the way task budgets are.
+pub fn progress(
+    tasks: &[Task],
+    entries: &[TimeEntry],
+    records: &[Record],
+    catalog: &Catalog,
+    day: NaiveDate,
+    day_start_hour: u32,
+    now: DateTime<Local>,
+) -> Vec<TaskProgress> {
+    let day_start = pomodoro::start_of_day(day, day_start_hour);
+    let day_end = pomodoro::start_of_day(day + Duration::days(1), day_start_hour);
+    tasks
+        .iter()
+        .map(|task| {
+            let latest = entries.iter().find(|x| task.matches(x, catalog));
+            let spent_secs = match (latest, day_start, day_end) {
+                (Some(latest), Some(day_start), Some(day_end)) => entries
+                    .iter()
+                    .filter(|x| budget::is_same_task(x, latest))
+                    .map(|x| {
+                        let stop = x.stop.unwrap_or(if x.duration >= 0 {
+                            x.start + Duration::seconds(x.duration)
+                        } else {
+                            now
+                        });
+                        (stop.min(day_end) - x.start.max(day_start))
+                            .num_seconds()
+                            .max(0)
+                    })
+                    .sum(),
+                _ => 0,
+            };
+            let done = records
+                .iter()
+                .filter(|x| pomodoro::day_of(&x.start, day_start_hour) == day)
+                .filter(|x| x.is_pomodoro() && task.matches_record(x))
+                .count() as u32;
+            TaskProgress {
+                task: task.clone(),
+                done,
+                spent_secs,
+            }
+        })
+        .collect()
+}
+
+/// Returns the first task that has pomodoros left.
+pub fn next(progress: &[TaskProgress]) -> Option<&TaskProgress> {
+    progress.iter().find(|x| x.done < x.task.estimate)
+}
+
+/// Compares estimates with actuals, one line per task.
+pub fn review(progress: &[TaskProgress], pomodoro_min: u32) -> String {
+    let mut s = String::new();
+    let width = progress
+        .iter()
+        .map(|x| x.task.description.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max(4)
+        + 2;
+    let _ = writeln!(
+        s,
+        "{:<w$}{:>10}{:>6}{:>10}{:>8}",
+        "Task",
+        "Estimate",
+        "Done",
+        "Planned",
+        "Spent",
+        w = width
+    );
+    let (mut estimate, mut done, mut spent) = (0, 0, 0);
+    for p in progress {
+        let _ = writeln!(
+            s,
+            "{:<w$}{:>10}{:>6}{:>10}{:>8}",
+            p.task.description,
+            p.task.estimate,
+            p.done,
+            hm(p.task.estimate as i64 * pomodoro_min as i64 * 60),
+            hm(p.spent_secs),
+            w = width
+        );
+        estimate += p.task.estimate;
+        done += p.done;
+        spent += p.spent_secs;
+    }
+    let _ = writeln!(
+        s,
+        "{:<w$}{:>10}{:>6}{:>10}{:>8}",
+        "Total",
+        estimate,
+        done,
+        hm(estimate as i64 * pomodoro_min as i64 * 60),
+        hm(spent),
+        w = width
+    );
+    s
+}
+
+/// Summarizes the day in one line for notifiers.
+pub fn summary(day: NaiveDate, progress: &[TaskProgress]) -> String {
+    let estimate: u32 = progress.iter().map(|x| x.task.estimate).sum();
+    let done: u32 = progress.iter().map(|x| x.done).sum();
+    let finished = progress
+        .iter()
+        .filter(|x| x.done >= x.task.estimate)
+        .count();
+    format!(
+        "Plan for {}: {}/{} pomodoros, {} of {} tasks done",
+        day,
+        done,
+        estimate,
+        finished,
+        progress.len()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::Outcome;
+    use crate::pomodoro::PomodoroMode;
+    use chrono::TimeZone;
+
+    fn at(d: u32, h: u32, m: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2024, 5, d, h, m, 0).unwrap()
+    }
+
+    fn entry(id: u64, description: &str, start: DateTime<Local>, min: Option<i64>) -> TimeEntry {
+        let mut entry = TimeEntry::new(id, description, Some("book"), &[], start);
+        if let Some(min) = min {
+            entry.stop_at(start + Duration::minutes(min));
+        }
+        entry
+    }
+
+    fn record(description: &str, start: DateTime<Local>, outcome: Outcome) -> Record {
+        Record {
+            entry_id: 1,
+            entry_ids: vec![1],
+            mode: PomodoroMode::Work,
+            outcome,
+            description: description.to_string(),
+            project: Some("book".to_string()),
+            tags: Vec::new(),
+            start,
+            end: start + Duration::minutes(25),
+            planned_secs: 25 * 60,
+            actual_secs: 25 * 60,
+            count: 1,
+            long_break: false,
+            notifications: 0,
+            interrupted: false,
+            interruptions: Vec::new(),
+            voided: false,
+        }
+    }
+
+    fn task(line: &str) -> Task {
+        Task::parse(line).unwrap()
+    }
+
+    #[test]
+    fn tasks_are_parsed_from_plan_lines() {
+        let t = task("3 write chapter two @My Book #draft #deep");
+        assert_eq!(t.estimate, 3);
+        assert_eq!(t.description, "write chapter two");
+        assert_eq!(t.project.as_deref(), Some("My Book"));
+        assert_eq!(t.tags, vec!["draft", "deep"]);
+        assert_eq!(t.to_line(), "3 write chapter two @My Book #draft #deep");
+
+        let t = task("  1 mail  ");
+        assert_eq!((t.estimate, t.description.as_str()), (1, "mail"));
+        assert_eq!(t.project, None);
+        assert!(Task::parse("two write").is_err());
+        assert!(Task::parse("-1 write").is_err());
+    }
+
+    #[test]
+    fn task_args_end_the_project_at_a_tag() {
+        assert_eq!(
+            parse_task_args("review #ops @Big Client #urgent"),
+            (
+                "review".to_string(),
+                Some("Big Client".to_string()),
+                vec!["ops".to_string(), "urgent".to_string()]
+            )
+        );
+        assert_eq!(
+            parse_task_args("read"),
+            ("read".to_string(), None, Vec::new())
+        );
+    }
+
+    #[test]
+    fn progress_sums_the_day_of_each_task() {
+        let catalog = Catalog::default();
+        let day = NaiveDate::from_ymd_opt(2024, 5, 2).unwrap();
+        let tasks = vec![task("3 write @Book"), task("1 read"), task("2 mail @other")];
+        // Newest first; the earliest entry began before the 4:00 day start.
+        let entries = vec![
+            entry(4, "write", at(2, 11, 0), None),
+            entry(3, "read", at(2, 10, 0), Some(20)),
+            entry(2, "write", at(2, 9, 0), Some(50)),
+            entry(1, "write", at(2, 3, 0), Some(90)),
+        ];
+        let records = vec![
+            record("write", at(1, 10, 0), Outcome::Completed),
+            record("write", at(2, 9, 0), Outcome::Completed),
+            record("write", at(2, 9, 30), Outcome::Stopped),
+            record("write", at(2, 11, 0), Outcome::Overrun),
+            record("read", at(2, 10, 0), Outcome::Stopped),
+        ];
+        let progress = progress(&tasks, &entries, &records, &catalog, day, 4, at(2, 11, 30));
+
+        assert_eq!(progress[0].done, 2);
+        assert_eq!(progress[0].spent_secs, (30 + 50 + 30) * 60);
+        assert_eq!(progress[1].done, 0);
+        assert_eq!(progress[1].spent_secs, 20 * 60);
+        assert_eq!(progress[2].done, 0);
+        assert_eq!(progress[2].spent_secs, 0);
+
+        assert_eq!(
+            summary(day, &progress),
+            "Plan for 2024-05-02: 2/6 pomodoros, 0 of 3 tasks done"
+        );
+    }
+
+    #[test]
+    fn next_skips_finished_tasks() {
+        let progress = |line: &str, done| TaskProgress {
+            task: task(line),
+            done,
+            spent_secs: 0,
+        };
+        let plan = vec![
+            progress("1 mail", 1),
+            progress("2 write", 3),
+            progress("2 read", 1),
+        ];
+        assert_eq!(next(&plan).unwrap().task.description, "read");
+        assert_eq!(
+            summary(NaiveDate::from_ymd_opt(2024, 5, 2).unwrap(), &plan),
+            "Plan for 2024-05-02: 5/5 pomodoros, 2 of 3 tasks done"
+        );
+        assert!(next(&plan[..2]).is_none());
+    }
+}
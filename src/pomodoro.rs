@@ -1,6 +1,367 @@
-#[derive(Clone, Copy, Debug, PartialEq)]
+use std::collections::HashSet;
+
+use chrono::{DateTime, Duration, Local, NaiveDate, TimeZone};
+use failure::Error;
+use regex::Regex;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::catalog::Catalog;
+use crate::config::{MatchConfig, PomodoroConfig, PomodoroProfile};
+use crate::toggl::TimeEntry;
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
 pub enum PomodoroMode {
     Idle,
     Work,
     Break,
 }
+
+/// Returns the date `t` belongs to when days start at `day_start_hour`.
+pub fn day_of(t: &DateTime<Local>, day_start_hour: u32) -> NaiveDate {
+    (*t - Duration::hours(day_start_hour as i64)).date_naive()
+}
+
+/// Returns the instant the given day starts at when days start at
+/// `day_start_hour`.
+pub fn start_of_day(date: NaiveDate, day_start_hour: u32) -> Option<DateTime<Local>> {
+    Local
+        .from_local_datetime(&date.and_hms_opt(day_start_hour, 0, 0)?)
+        .earliest()
+}
+
+/// How an entry relates to the stopped entry before it.
+pub enum Link {
+    /// The entry continues the same set.
+    Continue,
+    /// The entry continues the set after an untracked gap counted as break.
+    Gap(i64),
+    /// The entry starts a new set.
+    Reset,
+}
+
+/// Decides whether an entry starting at `start` continues the set of the
+/// earlier entry.
+pub fn link(config: &PomodoroConfig, earlier: &TimeEntry, start: &DateTime<Local>) -> Link {
+    let stop = match earlier.stop {
+        Some(stop) => stop,
+        None => return Link::Reset,
+    };
+    if config.reset_on_new_day
+        && day_of(&earlier.start, config.day_start_hour) != day_of(start, config.day_start_hour)
+    {
+        return Link::Reset;
+    }
+
+    let gap = (*start - stop).num_seconds();
+    if gap <= config.max_gap_sec as i64 {
+        return Link::Continue;
+    }
+    match config.reset_idle_min {
+        Some(idle_min) if gap < idle_min as i64 * 60 => Link::Gap(gap),
+        _ => Link::Reset,
+    }
+}
+
+/// Returns the length in seconds of a break that ends the set.
+pub fn reset_break_secs(config: &PomodoroConfig, lengths: &Lengths) -> i64 {
+    config.reset_break_min.unwrap_or(lengths.long_break_min) as i64 * 60
+}
+
+/// Appends a phase to the history, merging it with the last one if both
+/// have the same mode.
+pub fn add_history(history: &mut Vec<(PomodoroMode, i64)>, mode: PomodoroMode, secs: i64) {
+    match history.last_mut() {
+        Some(ref mut v) if v.0 == mode => **v = (v.0, v.1 + secs),
+        _ => history.push((mode, secs)),
+    }
+}
+
+/// Returns true if the history ends with a break long enough to end the set.
+pub fn is_reset(history: &[(PomodoroMode, i64)], reset_break_secs: i64) -> bool {
+    match history.last() {
+        Some(&(PomodoroMode::Break, d)) => d >= reset_break_secs,
+        _ => false,
+    }
+}
+
+/// Returns the number of work phases in the history that lasted at least
+/// `pomodoro_secs`.  Voided pomodoros are recorded as `Idle` and never count.
+pub fn count_completed(history: &[(PomodoroMode, i64)], pomodoro_secs: i64) -> u32 {
+    history
+        .iter()
+        .filter(|x| x.0 == PomodoroMode::Work && x.1 >= pomodoro_secs)
+        .count() as u32
+}
+
+/// Tag prefix that selects a profile by name, e.g. `profile:deep`.
+const PROFILE_TAG_PREFIX: &str = "profile:";
+
+#[derive(Clone, Copy, Debug)]
+pub struct Lengths {
+    pub pomodoro_min: u32,
+    pub short_break_min: u32,
+    pub long_break_min: u32,
+    pub long_break_after: u32,
+}
+
+impl Lengths {
+    fn with_profile(self, profile: &PomodoroProfile) -> Self {
+        Lengths {
+            pomodoro_min: profile.pomodoro_min.unwrap_or(self.pomodoro_min),
+            short_break_min: profile.short_break_min.unwrap_or(self.short_break_min),
+            long_break_min: profile.long_break_min.unwrap_or(self.long_break_min),
+            long_break_after: profile.long_break_after.unwrap_or(self.long_break_after),
+        }
+    }
+
+    pub fn break_min(&self, npomodoros: u32) -> u32 {
+        if npomodoros >= self.long_break_after {
+            self.long_break_min
+        } else {
+            self.short_break_min
+        }
+    }
+}
+
+impl From<&PomodoroConfig> for Lengths {
+    fn from(config: &PomodoroConfig) -> Self {
+        Lengths {
+            pomodoro_min: config.pomodoro_min,
+            short_break_min: config.short_break_min,
+            long_break_min: config.long_break_min,
+            long_break_after: config.long_break_after,
+        }
+    }
+}
+
+fn profile_matches(profile: &PomodoroProfile, entry: &TimeEntry, catalog: &Catalog) -> bool {
+    if let Some(project) = catalog.project_name(entry) {
+        if profile.projects.contains(&project) {
+            return true;
+        }
+    }
+    if let Some(client) = catalog.client_name(entry) {
+        if profile.clients.contains(&client) {
+            return true;
+        }
+    }
+    if entry.tags.iter().any(|x| profile.tags.contains(x)) {
+        return true;
+    }
+    profile
+        .description
+        .as_ref()
+        .is_some_and(|x| x.is_match(&entry.description))
+}
+
+/// Returns the lengths for the given entry.  A `profile:<name>` tag takes
+/// precedence; otherwise the first matching profile is used, falling back to
+/// the global settings.
+pub fn lengths_for(config: &PomodoroConfig, entry: &TimeEntry, catalog: &Catalog) -> Lengths {
+    let lengths = Lengths::from(config);
+
+    for tag in &entry.tags {
+        if let Some(name) = tag.strip_prefix(PROFILE_TAG_PREFIX) {
+            if let Some(profile) = config.profiles.iter().find(|x| x.name == name) {
+                return lengths.with_profile(profile);
+            }
+        }
+    }
+    for profile in &config.profiles {
+        if profile_matches(profile, entry, catalog) {
+            return lengths.with_profile(profile);
+        }
+    }
+    lengths
+}
+
+struct Matcher {
+    description: Option<Regex>,
+    tags: Vec<String>,
+    projects: Vec<String>,
+    project_ids: Vec<u64>,
+}
+
+impl Matcher {
+    fn new(config: &MatchConfig) -> Self {
+        Matcher {
+            description: config.description.clone(),
+            tags: config.tags.clone(),
+            projects: config.projects.clone(),
+            project_ids: config.project_ids.clone(),
+        }
+    }
+
+    fn matches(&self, entry: &TimeEntry, catalog: &Catalog) -> bool {
+        if let Some(ref re) = self.description {
+            if re.is_match(&entry.description) {
+                return true;
+            }
+        }
+        if entry.tags.iter().any(|x| self.tags.contains(x)) {
+            return true;
+        }
+        if let Some(id) = entry.project_id {
+            if self.project_ids.contains(&id) {
+                return true;
+            }
+        }
+        match catalog.project_name(entry) {
+            Some(name) => self.projects.contains(&name),
+            None => false,
+        }
+    }
+}
+
+/// Decides whether entries are work, breaks or ignored altogether.
+pub struct Classifier<'a> {
+    break_match: Matcher,
+    ignore: Matcher,
+    catalog: &'a Catalog,
+}
+
+impl<'a> Classifier<'a> {
+    pub fn new(config: &PomodoroConfig, catalog: &'a Catalog) -> Self {
+        Classifier {
+            break_match: Matcher::new(&config.break_match),
+            ignore: Matcher::new(&config.ignore),
+            catalog,
+        }
+    }
+
+    /// Returns the mode of the entry, or `None` if it should count as
+    /// neither work nor break.
+    pub fn mode_of(&self, entry: &TimeEntry) -> Option<PomodoroMode> {
+        if self.ignore.matches(entry, self.catalog) {
+            None
+        } else if self.break_match.matches(entry, self.catalog) {
+            Some(PomodoroMode::Break)
+        } else {
+            Some(PomodoroMode::Work)
+        }
+    }
+}
+
+/// The phase of the running entry, worked out from the entries before it.
+pub struct Phase {
+    pub mode: PomodoroMode,
+    /// Position of the pomodoro in its set.
+    pub count: u32,
+    pub lengths: Lengths,
+    /// Planned length of the phase in seconds.
+    pub planned_secs: i64,
+    pub finish_time: DateTime<Local>,
+    /// True if earlier entries are part of the same phase.
+    pub continued: bool,
+    /// True if no work counted since the reset before the phase, so a
+    /// pomodoro here starts a new set.
+    pub reset: bool,
+}
+
+/// Works out the phase of the running entry from `entries` ordered newest
+/// first.  Returns `None` if no entry is running or the running one is
+/// ignored.  Work entries in `voided` count as neither work nor break.
+pub fn current_phase(
+    config: &PomodoroConfig,
+    catalog: &Catalog,
+    entries: &[TimeEntry],
+    voided: &HashSet<u64>,
+) -> Result<Option<Phase>, Error> {
+    match entries.first() {
+        Some(entry) if entry.duration < 0 => phase_of(config, catalog, entries, voided),
+        _ => Ok(None),
+    }
+}
+
+/// Works out the phase `entries[0]` belongs to from the entries before it,
+/// whether it is running or not.  Reports walk past entries with this, so
+/// they split sets exactly like the running one.
+pub fn phase_of(
+    config: &PomodoroConfig,
+    catalog: &Catalog,
+    entries: &[TimeEntry],
+    voided: &HashSet<u64>,
+) -> Result<Option<Phase>, Error> {
+    let latest_entry = match entries.first() {
+        Some(entry) => entry,
+        None => return Ok(None),
+    };
+    let classifier = Classifier::new(config, catalog);
+    let latest_mode = match classifier.mode_of(latest_entry) {
+        Some(mode) => mode,
+        None => return Ok(None),
+    };
+    let lengths = lengths_for(config, latest_entry, catalog);
+    let reset_break_secs = reset_break_secs(config, &lengths);
+    let mut history: Vec<(PomodoroMode, i64)> = Vec::new();
+    let mut last_start = &latest_entry.start;
+
+    for x in &entries[1..] {
+        match link(config, x, last_start) {
+            Link::Continue => (),
+            Link::Gap(gap) => {
+                add_history(&mut history, PomodoroMode::Break, gap);
+                if is_reset(&history, reset_break_secs) {
+                    history.pop();
+                    break;
+                }
+            }
+            Link::Reset => break,
+        }
+
+        let mode = match classifier.mode_of(x) {
+            Some(mode) => mode,
+            None => {
+                last_start = &x.start;
+                continue;
+            }
+        };
+        let mode = if mode == PomodoroMode::Work && voided.contains(&x.id) {
+            PomodoroMode::Idle
+        } else {
+            mode
+        };
+
+        add_history(&mut history, mode, x.duration);
+        if is_reset(&history, reset_break_secs) {
+            history.pop();
+            break;
+        }
+
+        last_start = &x.start;
+    }
+
+    let pomodoro_secs = lengths.pomodoro_min as i64 * 60;
+    let continued = history.first().is_some_and(|v| v.0 == latest_mode);
+    // Leave out the earlier part of the running phase, if any.
+    let earlier = if continued {
+        &history[1..]
+    } else {
+        &history[..]
+    };
+    let reset = earlier.iter().all(|x| x.0 != PomodoroMode::Work);
+    let count = if latest_mode == PomodoroMode::Work {
+        count_completed(earlier, pomodoro_secs) + 1
+    } else {
+        count_completed(&history, pomodoro_secs)
+    };
+    let planned_secs = if latest_mode == PomodoroMode::Break {
+        lengths.break_min(count) as i64 * 60
+    } else {
+        pomodoro_secs
+    };
+    let mut duration = planned_secs;
+    if continued {
+        duration -= history[0].1;
+    }
+
+    Ok(Some(Phase {
+        mode: latest_mode,
+        count,
+        lengths,
+        planned_secs,
+        finish_time: latest_entry.start + Duration::seconds(duration),
+        continued,
+        reset,
+    }))
+}
@@ -0,0 +1,401 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write;
+
+use chrono::{DateTime, Local, NaiveDate};
+use failure::Error;
+use serde_derive::Serialize;
+
+use crate::catalog::Catalog;
+use crate::config::PomodoroConfig;
+use crate::duration::hm;
+use crate::history::{InterruptionKind, Outcome, Record};
+use crate::pomodoro::{self, Link, Phase, PomodoroMode};
+use crate::toggl::TimeEntry;
+
+#[derive(Debug, Default, Serialize)]
+pub struct Breakdown {
+    pub pomodoros: u32,
+    pub work_secs: i64,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct Report {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub pomodoros: u32,
+    pub sets: u32,
+    pub short_breaks: u32,
+    pub long_breaks: u32,
+    pub work_secs: i64,
+    pub break_secs: i64,
+    pub overwork_secs: i64,
+    pub overbreak_secs: i64,
+    pub voided: u32,
+    pub internal_interruptions: u32,
+    pub external_interruptions: u32,
+    pub projects: BTreeMap<String, Breakdown>,
+    pub tags: BTreeMap<String, Breakdown>,
+}
+
+impl Report {
+    /// Adds the voided pomodoros and interruptions recorded in the local
+    /// history, which the tracker does not know about.
+    fn add_records(&mut self, records: &[Record]) {
+        for record in records {
+            if record.outcome == Outcome::Voided {
+                self.voided += 1;
+            }
+            self.internal_interruptions += record.count_interruptions(InterruptionKind::Internal);
+            self.external_interruptions += record.count_interruptions(InterruptionKind::External);
+        }
+    }
+}
+
+impl Report {
+    fn add_work(&mut self, name: String, is_project: bool, secs: i64, completed: bool) {
+        let map = if is_project {
+            &mut self.projects
+        } else {
+            &mut self.tags
+        };
+        let b = map.entry(name).or_default();
+        b.work_secs += secs;
+        if completed {
+            b.pomodoros += 1;
+        }
+    }
+
+    /// Adds the excess of a finished phase over its plan.
+    fn close_phase(&mut self, phase: Option<(Phase, i64)>) {
+        match phase {
+            Some((ref p, secs)) if p.mode == PomodoroMode::Work => {
+                self.overwork_secs += (secs - p.planned_secs).max(0);
+            }
+            Some((ref p, secs)) if p.mode == PomodoroMode::Break => {
+                self.overbreak_secs += (secs - p.planned_secs).max(0);
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Builds a report from entries ordered newest first, as Toggl returns them.
+/// `records` are the local history of the same period.  Each entry is
+/// placed with `pomodoro::phase_of`, the cycle logic of the running phase.
+pub fn build(
+    entries: &[TimeEntry],
+    records: &[Record],
+    config: &PomodoroConfig,
+    catalog: &Catalog,
+    now: DateTime<Local>,
+) -> Result<Report, Error> {
+    let mut report: Report = Default::default();
+    let voided: HashSet<u64> = records
+        .iter()
+        .filter(|x| x.voided)
+        .flat_map(|x| x.all_entry_ids().collect::<Vec<_>>())
+        .collect();
+    // The phase being walked and the seconds spent in it so far.
+    let mut phase: Option<(Phase, i64)> = None;
+
+    for i in (0..entries.len()).rev() {
+        let entry = &entries[i];
+        if let Some(earlier) = entries.get(i + 1) {
+            if let Link::Gap(gap) = pomodoro::link(config, earlier, &entry.start) {
+                report.break_secs += gap;
+            }
+        }
+        let p = match pomodoro::phase_of(config, catalog, &entries[i..], &voided)? {
+            Some(p) if !(p.mode == PomodoroMode::Work && voided.contains(&entry.id)) => p,
+            _ => continue,
+        };
+        let secs = if entry.duration >= 0 {
+            entry.duration
+        } else {
+            (now - entry.start).num_seconds()
+        };
+
+        let same = p.continued && phase.as_ref().is_some_and(|x| x.0.mode == p.mode);
+        if !same {
+            report.close_phase(phase.take());
+            match p.mode {
+                PomodoroMode::Work if p.reset => report.sets += 1,
+                PomodoroMode::Break if p.count >= p.lengths.long_break_after => {
+                    report.long_breaks += 1
+                }
+                PomodoroMode::Break => report.short_breaks += 1,
+                _ => (),
+            }
+            phase = Some((p, 0));
+        }
+        let (ref p, ref mut total) = *phase.as_mut().unwrap();
+        let before = *total;
+        *total += secs;
+
+        match p.mode {
+            PomodoroMode::Work => {
+                // Same rule as `pomodoro::count_completed`.
+                let pomodoro_secs = p.lengths.pomodoro_min as i64 * 60;
+                let completed = before < pomodoro_secs && *total >= pomodoro_secs;
+                report.work_secs += secs;
+                if completed {
+                    report.pomodoros += 1;
+                }
+                let project = catalog
+                    .project_name(entry)
+                    .unwrap_or_else(|| "(no project)".to_string());
+                report.add_work(project, true, secs, completed);
+                for tag in &entry.tags {
+                    report.add_work(tag.clone(), false, secs, completed);
+                }
+            }
+            PomodoroMode::Break => report.break_secs += secs,
+            PomodoroMode::Idle => (),
+        }
+    }
+    report.close_phase(phase);
+    report.add_records(records);
+
+    Ok(report)
+}
+
+/// Builds a report from the local history, oldest first.
+pub fn from_records(records: &[Record]) -> Report {
+    let mut report: Report = Default::default();
+
+    for record in records {
+        let excess = (record.actual_secs - record.planned_secs).max(0);
+        match record.mode {
+            PomodoroMode::Work => {
+                let completed = record.is_pomodoro();
+                report.work_secs += record.actual_secs;
+                report.overwork_secs += excess;
+                if record.count == 1 {
+                    report.sets += 1;
+                }
+                if completed {
+                    report.pomodoros += 1;
+                }
+
+                let project = record
+                    .project
+                    .clone()
+                    .unwrap_or_else(|| "(no project)".to_string());
+                let names = std::iter::once((true, project))
+                    .chain(record.tags.iter().map(|x| (false, x.clone())));
+                for (is_project, name) in names {
+                    let map = if is_project {
+                        &mut report.projects
+                    } else {
+                        &mut report.tags
+                    };
+                    let b = map.entry(name).or_default();
+                    b.work_secs += record.actual_secs;
+                    if completed {
+                        b.pomodoros += 1;
+                    }
+                }
+            }
+            PomodoroMode::Break => {
+                report.break_secs += record.actual_secs;
+                report.overbreak_secs += excess;
+                if record.long_break {
+                    report.long_breaks += 1;
+                } else {
+                    report.short_breaks += 1;
+                }
+            }
+            PomodoroMode::Idle => (),
+        }
+    }
+    report.add_records(records);
+    report
+}
+
+impl Report {
+    pub fn to_table(&self) -> String {
+        let mut s = String::new();
+        if let (Some(from), Some(to)) = (self.from, self.to) {
+            let _ = writeln!(s, "{:<16}{} .. {}", "Period", from, to);
+        }
+        let _ = writeln!(s, "{:<16}{}", "Pomodoros", self.pomodoros);
+        let _ = writeln!(s, "{:<16}{}", "Sets", self.sets);
+        let _ = writeln!(s, "{:<16}{}", "Short breaks", self.short_breaks);
+        let _ = writeln!(s, "{:<16}{}", "Long breaks", self.long_breaks);
+        let _ = writeln!(s, "{:<16}{}", "Work", hm(self.work_secs));
+        let _ = writeln!(s, "{:<16}{}", "Break", hm(self.break_secs));
+        let _ = writeln!(s, "{:<16}{}", "Overwork", hm(self.overwork_secs));
+        let _ = writeln!(s, "{:<16}{}", "Overbreak", hm(self.overbreak_secs));
+        let _ = writeln!(s, "{:<16}{}", "Voided", self.voided);
+        let _ = writeln!(
+            s,
+            "{:<16}{} internal, {} external",
+            "Interruptions", self.internal_interruptions, self.external_interruptions
+        );
+
+        for (title, map) in &[("Project", &self.projects), ("Tag", &self.tags)] {
+            if map.is_empty() {
+                continue;
+            }
+            let width = map.keys().map(|x| x.chars().count()).max().unwrap_or(0);
+            let width = width.max(title.len()) + 2;
+            let _ = writeln!(s);
+            let _ = writeln!(
+                s,
+                "{:<w$}{:>10}{:>8}",
+                title,
+                "Pomodoros",
+                "Work",
+                w = width
+            );
+            for (name, b) in map.iter() {
+                let _ = writeln!(
+                    s,
+                    "{:<w$}{:>10}{:>8}",
+                    name,
+                    b.pomodoros,
+                    hm(b.work_secs),
+                    w = width
+                );
+            }
+        }
+        s
+    }
+
+    pub fn to_csv(&self) -> String {
+        fn quote(s: &str) -> String {
+            if s.contains([',', '"', '\n']) {
+                format!("\"{}\"", s.replace('"', "\"\""))
+            } else {
+                s.to_string()
+            }
+        }
+
+        let mut s = String::new();
+        let _ = writeln!(
+            s,
+            "scope,name,pomodoros,sets,short_breaks,long_breaks,\
+             work_min,break_min,overwork_min,overbreak_min,\
+             voided,internal_interruptions,external_interruptions"
+        );
+        let _ = writeln!(
+            s,
+            "total,,{},{},{},{},{},{},{},{},{},{},{}",
+            self.pomodoros,
+            self.sets,
+            self.short_breaks,
+            self.long_breaks,
+            self.work_secs / 60,
+            self.break_secs / 60,
+            self.overwork_secs / 60,
+            self.overbreak_secs / 60,
+            self.voided,
+            self.internal_interruptions,
+            self.external_interruptions
+        );
+        for (scope, map) in &[("project", &self.projects), ("tag", &self.tags)] {
+            for (name, b) in map.iter() {
+                let _ = writeln!(
+                    s,
+                    "{},{},{},,,,{},,,,,,",
+                    scope,
+                    quote(name),
+                    b.pomodoros,
+                    b.work_secs / 60
+                );
+            }
+        }
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    fn entry(id: u64, description: &str, start: DateTime<Local>, min: i64) -> TimeEntry {
+        let mut entry = TimeEntry::new(id, description, None, &[], start);
+        entry.stop_at(start + Duration::minutes(min));
+        entry
+    }
+
+    #[test]
+    fn report_follows_the_running_cycle_logic() {
+        let config = PomodoroConfig::default();
+        let catalog = Catalog::default();
+        let at = |h, m| Local.with_ymd_and_hms(2024, 5, 2, h, m, 0).unwrap();
+        // Newest first: a pomodoro split over two entries, a break, a
+        // pomodoro, and a short one before a long gap ending the set.
+        let entries = vec![
+            entry(5, "b", at(10, 10), 15),
+            entry(4, "b", at(10, 0), 10),
+            entry(3, "Pomodoro Break", at(9, 55), 5),
+            entry(2, "a", at(9, 30), 25),
+            entry(1, "a", at(8, 0), 10),
+        ];
+        let report = build(&entries, &[], &config, &catalog, at(11, 0)).unwrap();
+        assert_eq!(report.pomodoros, 2);
+        assert_eq!(report.sets, 2);
+        assert_eq!(report.short_breaks, 1);
+        assert_eq!(report.work_secs, 60 * 60);
+        assert_eq!(report.projects["(no project)"].pomodoros, 2);
+
+        let phase = pomodoro::phase_of(&config, &catalog, &entries, &HashSet::new())
+            .unwrap()
+            .unwrap();
+        assert_eq!(phase.count, 2);
+        assert!(phase.continued);
+    }
+
+    #[test]
+    fn stopped_pomodoro_does_not_start_another_set() {
+        let config = PomodoroConfig::default();
+        let catalog = Catalog::default();
+        let at = |h, m| Local.with_ymd_and_hms(2024, 5, 2, h, m, 0).unwrap();
+        // Newest first: a pomodoro after a break that followed one stopped
+        // early, so both are the first of the same set.
+        let entries = vec![
+            entry(3, "a", at(9, 15), 25),
+            entry(2, "Pomodoro Break", at(9, 10), 5),
+            entry(1, "a", at(9, 0), 10),
+        ];
+        let report = build(&entries, &[], &config, &catalog, at(10, 0)).unwrap();
+        assert_eq!(report.pomodoros, 1);
+        assert_eq!(report.sets, 1);
+
+        let phase = pomodoro::phase_of(&config, &catalog, &entries, &HashSet::new())
+            .unwrap()
+            .unwrap();
+        assert_eq!(phase.count, 1);
+        assert!(!phase.reset);
+    }
+
+    #[test]
+    fn voided_pomodoro_is_left_out_with_all_its_entries() {
+        let config = PomodoroConfig::default();
+        let catalog = Catalog::default();
+        let at = |h, m| Local.with_ymd_and_hms(2024, 5, 2, h, m, 0).unwrap();
+        // Newest first: a pomodoro after a break that followed one voided
+        // pomodoro split over two entries.
+        let entries = vec![
+            entry(4, "a", at(9, 30), 25),
+            entry(3, "Pomodoro Break", at(9, 25), 5),
+            entry(2, "a", at(9, 10), 15),
+            entry(1, "a", at(9, 0), 10),
+        ];
+        let record: Record = serde_json::from_str(
+            r#"{"entry_id":2,"entry_ids":[1,2],"mode":"Work","outcome":"voided",
+                "description":"a","project":null,"tags":[],
+                "start":"2024-05-02T09:00:00+02:00","end":"2024-05-02T09:25:00+02:00",
+                "planned_secs":1500,"actual_secs":1500,"count":1,"long_break":false,
+                "notifications":0,"voided":true}"#,
+        )
+        .unwrap();
+        let report = build(&entries, &[record], &config, &catalog, at(10, 0)).unwrap();
+        assert_eq!(report.pomodoros, 1);
+        assert_eq!(report.voided, 1);
+        assert_eq!(report.sets, 1);
+        assert_eq!(report.work_secs, 25 * 60);
+    }
+}
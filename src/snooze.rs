@@ -0,0 +1,96 @@
+use std::fs::{self, File};
+use std::io::{BufReader, Write};
+use std::path::PathBuf;
+
+use chrono::{DateTime, Duration, Local};
+use failure::{format_err, Error};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::config;
+
+/// Snooze and pause offsets of the current phase, applied on top of its
+/// computed finish time.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Offsets {
+    /// Start of the phase the offsets belong to.
+    pub phase_start: Option<DateTime<Local>>,
+    pub snoozed_secs: i64,
+    pub paused_secs: i64,
+    pub paused_at: Option<DateTime<Local>>,
+}
+
+fn path() -> PathBuf {
+    config::data_dir().join("offsets.json")
+}
+
+impl Offsets {
+    pub fn load() -> Result<Self, Error> {
+        match File::open(path()) {
+            Ok(file) => Ok(serde_json::from_reader(BufReader::new(file))?),
+            Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Default::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        let path = path();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let tmp = path.with_extension("json.tmp");
+        let mut file = File::create(&tmp)?;
+        file.write_all(serde_json::to_string(self)?.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(tmp, path)?;
+        Ok(())
+    }
+
+    /// Resets the offsets when a new phase starts.  Returns true if they
+    /// changed.
+    pub fn for_phase(&mut self, phase_start: DateTime<Local>) -> bool {
+        if self.phase_start == Some(phase_start) {
+            return false;
+        }
+        *self = Offsets {
+            phase_start: Some(phase_start),
+            ..Default::default()
+        };
+        true
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused_at.is_some()
+    }
+
+    /// Returns how far the finish time moves, counting an ongoing pause up
+    /// to `now`.
+    pub fn total(&self, now: DateTime<Local>) -> Duration {
+        let pausing = self.paused_at.map_or(Duration::zero(), |x| now - x);
+        Duration::seconds(self.snoozed_secs + self.paused_secs) + pausing
+    }
+
+    pub fn snooze(&mut self, d: Duration) -> Result<(), Error> {
+        if d < Duration::zero() {
+            return Err(format_err!("cannot snooze by a negative duration"));
+        }
+        self.snoozed_secs += d.num_seconds();
+        Ok(())
+    }
+
+    pub fn pause(&mut self, now: DateTime<Local>) -> Result<(), Error> {
+        if self.is_paused() {
+            return Err(format_err!("already paused"));
+        }
+        self.paused_at = Some(now);
+        Ok(())
+    }
+
+    pub fn resume(&mut self, now: DateTime<Local>) -> Result<(), Error> {
+        let paused_at = self
+            .paused_at
+            .take()
+            .ok_or_else(|| format_err!("not paused"))?;
+        self.paused_secs += (now - paused_at).num_seconds();
+        Ok(())
+    }
+}
@@ -0,0 +1,56 @@
+use chrono::{DateTime, Local};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::history::Record;
+use crate::pomodoro::PomodoroMode;
+
+/// The daemon state served as JSON to the HTTP API, event streams and
+/// `status json` on the socket.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Status {
+    pub mode: PomodoroMode,
+    pub count: u32,
+    /// Pomodoros in a set before the long break.
+    pub long_break_after: u32,
+    pub description: String,
+    pub project: String,
+    pub client: String,
+    pub phase_start: Option<DateTime<Local>>,
+    pub finish_time: Option<DateTime<Local>>,
+    pub task_finish_time: Option<DateTime<Local>>,
+    pub snoozed_secs: i64,
+    pub paused: bool,
+    pub task_done: Option<u32>,
+    pub task_estimate: Option<u32>,
+}
+
+/// Today's finished phases and the running one.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Today {
+    pub start: DateTime<Local>,
+    pub end: DateTime<Local>,
+    pub records: Vec<Record>,
+    pub phase: Option<Record>,
+}
+
+/// A task worked on recently, offered for restarting.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct RecentTask {
+    pub description: String,
+    pub project: Option<String>,
+    pub tags: Vec<String>,
+}
+
+impl RecentTask {
+    /// Returns the arguments of the `start` command for the task.
+    pub fn to_start_args(&self) -> String {
+        let mut s = self.description.clone();
+        if let Some(ref project) = self.project {
+            s += &format!(" @{}", project);
+        }
+        for tag in &self.tags {
+            s += &format!(" #{}", tag);
+        }
+        s
+    }
+}
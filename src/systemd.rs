@@ -0,0 +1,67 @@
+use std::env;
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::io::FromRawFd;
+use std::os::unix::net::{SocketAddr, UnixDatagram, UnixListener};
+use std::time::Duration;
+
+use failure::{format_err, Error};
+
+/// The first file descriptor systemd passes to socket-activated services.
+const LISTEN_FDS_START: i32 = 3;
+
+/// Takes the listener passed by systemd socket activation, if any.
+pub fn listen_fds() -> Result<Option<UnixListener>, Error> {
+    let pid = env::var("LISTEN_PID")
+        .ok()
+        .and_then(|x| x.parse::<u32>().ok());
+    let fds = env::var("LISTEN_FDS")
+        .ok()
+        .and_then(|x| x.parse::<i32>().ok());
+    env::remove_var("LISTEN_PID");
+    env::remove_var("LISTEN_FDS");
+    env::remove_var("LISTEN_FDNAMES");
+
+    if pid != Some(std::process::id()) {
+        return Ok(None);
+    }
+    match fds {
+        Some(1) => {
+            // The descriptor is ours from now on and nothing else uses it.
+            let listener = unsafe { UnixListener::from_raw_fd(LISTEN_FDS_START) };
+            Ok(Some(listener))
+        }
+        Some(n) if n > 1 => Err(format_err!("expected one socket from systemd, got {}", n)),
+        _ => Ok(None),
+    }
+}
+
+/// Sends a state such as `READY=1` to the service manager.  Does nothing
+/// when not started by systemd.
+pub fn notify(state: &str) -> Result<(), Error> {
+    let path = match env::var("NOTIFY_SOCKET") {
+        Ok(path) => path,
+        Err(_) => return Ok(()),
+    };
+    let socket = UnixDatagram::unbound()?;
+    let addr = match path.strip_prefix('@') {
+        Some(name) => SocketAddr::from_abstract_name(name)?,
+        None => SocketAddr::from_pathname(&path)?,
+    };
+    socket.send_to_addr(state.as_bytes(), &addr)?;
+    Ok(())
+}
+
+/// Returns how often to send `WATCHDOG=1` if the watchdog is enabled for
+/// this process: half the timeout, as systemd recommends.
+pub fn watchdog_interval() -> Option<Duration> {
+    if let Ok(pid) = env::var("WATCHDOG_PID") {
+        if pid.parse::<u32>().ok() != Some(std::process::id()) {
+            return None;
+        }
+    }
+    let usec: u64 = env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    if usec == 0 {
+        return None;
+    }
+    Some(Duration::from_micros(usec / 2))
+}
@@ -0,0 +1,156 @@
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::sync::RwLock;
+
+use chrono::{DateTime, Local};
+use failure::Error;
+use lazy_static::lazy_static;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::catalog::Catalog;
+use crate::config::{Config, Privacy, TeamConfig};
+use crate::pomodoro::{self, PomodoroMode};
+use crate::toggl::Toggl;
+
+/// A team member's state as far as their privacy setting allows.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct MemberStatus {
+    pub name: String,
+    /// True while the member is in a pomodoro.
+    pub focused: bool,
+    pub mode: Option<PomodoroMode>,
+    pub finish_time: Option<DateTime<Local>>,
+    pub description: Option<String>,
+    pub project: Option<String>,
+    /// Set when the member's entries could not be fetched.
+    pub error: Option<String>,
+}
+
+lazy_static! {
+    pub static ref BOARD: RwLock<Vec<MemberStatus>> = RwLock::new(Vec::new());
+}
+
+struct Member {
+    name: String,
+    privacy: Privacy,
+    toggl: Toggl,
+    catalog: Catalog,
+}
+
+impl Member {
+    fn status(&mut self, config: &Config) -> Result<MemberStatus, Error> {
+        if self.catalog.is_stale(config.catalog_refresh_min) {
+            self.catalog.apply(Catalog::fetch(&self.toggl));
+        }
+        let entries = self.toggl.time_entries()?;
+        let phase =
+            pomodoro::current_phase(&config.pomodoro, &self.catalog, &entries, &HashSet::new())?;
+
+        let mut status = MemberStatus {
+            name: self.name.clone(),
+            focused: phase.as_ref().is_some_and(|x| x.mode == PomodoroMode::Work),
+            mode: None,
+            finish_time: None,
+            description: None,
+            project: None,
+            error: None,
+        };
+        if self.privacy == Privacy::Busy {
+            return Ok(status);
+        }
+        status.mode = Some(phase.as_ref().map_or(PomodoroMode::Idle, |x| x.mode));
+        status.finish_time = phase.as_ref().map(|x| x.finish_time);
+        if self.privacy == Privacy::Full && phase.is_some() {
+            status.description = Some(entries[0].description.clone());
+            status.project = self.catalog.project_name(&entries[0]);
+        }
+        Ok(status)
+    }
+}
+
+/// Works out the phase of each team member with their own Toggl token.
+pub struct Team {
+    members: Vec<Member>,
+}
+
+impl Team {
+    pub fn new(config: &TeamConfig) -> Self {
+        let members = config
+            .members
+            .iter()
+            .map(|x| Member {
+                name: x.name.clone(),
+                privacy: x.privacy,
+                toggl: Toggl::new(x.token.clone()),
+                catalog: Default::default(),
+            })
+            .collect();
+        Team { members }
+    }
+
+    pub fn refresh(&mut self, config: &Config) -> Vec<MemberStatus> {
+        self.members
+            .iter_mut()
+            .map(|member| {
+                member.status(config).unwrap_or_else(|e| MemberStatus {
+                    name: member.name.clone(),
+                    focused: false,
+                    mode: None,
+                    finish_time: None,
+                    description: None,
+                    project: None,
+                    error: Some(e.to_string()),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Returns true if anyone's mode or focus changed between two boards.
+pub fn is_changed(before: &[MemberStatus], after: &[MemberStatus]) -> bool {
+    before.len() != after.len()
+        || before
+            .iter()
+            .zip(after.iter())
+            .any(|(a, b)| a.focused != b.focused || a.mode != b.mode)
+}
+
+/// Renders the board, one member per line.
+pub fn board(members: &[MemberStatus], now: DateTime<Local>) -> String {
+    let width = members
+        .iter()
+        .map(|x| x.name.chars().count())
+        .max()
+        .unwrap_or(0)
+        + 2;
+    let mut s = String::new();
+    for m in members {
+        let state = match (m.error.as_ref(), m.mode) {
+            (Some(e), _) => format!("error: {}", e),
+            (None, None) => if m.focused { "focused" } else { "available" }.to_string(),
+            (None, Some(mode)) => {
+                let mut state = format!("{:?}", mode);
+                if let Some(finish_time) = m.finish_time {
+                    let secs = (finish_time - now).num_seconds();
+                    let sign = if secs < 0 { "-" } else { "" };
+                    let _ = write!(
+                        state,
+                        " {}{:02}:{:02}",
+                        sign,
+                        secs.abs() / 60,
+                        secs.abs() % 60
+                    );
+                }
+                if let Some(ref description) = m.description {
+                    let _ = write!(state, " {}", description);
+                }
+                if let Some(ref project) = m.project {
+                    let _ = write!(state, " ({})", project);
+                }
+                state
+            }
+        };
+        let _ = writeln!(s, "{:<w$}{}", m.name, state, w = width);
+    }
+    s
+}
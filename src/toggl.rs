@@ -1,13 +1,22 @@
-use chrono::{DateTime, Local};
-use failure::Error;
+use std::time::Instant;
+
+use chrono::{DateTime, Local, Utc};
+use failure::{format_err, Error};
+use log::{debug, warn};
+use serde::de::DeserializeOwned;
 use serde_derive::{Deserialize, Serialize};
 
+use crate::catalog::{Catalog, CatalogUpdate};
+use crate::tracker::TimeTracker;
+
+const API_URL: &str = "https://api.track.toggl.com/api/v9";
+
 pub struct Toggl {
     token: String,
     client: reqwest::Client,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct TimeEntry {
     pub at: DateTime<Local>,
     pub billable: bool,
@@ -34,6 +43,113 @@ pub struct TimeEntry {
     pub workspace_id: u64,
 }
 
+impl TimeEntry {
+    /// Creates a running entry for trackers other than Toggl.
+    pub fn new(
+        id: u64,
+        description: &str,
+        project: Option<&str>,
+        tags: &[String],
+        start: DateTime<Local>,
+    ) -> Self {
+        TimeEntry {
+            at: start,
+            billable: false,
+            client_name: None,
+            description: description.to_string(),
+            duration: -1,
+            duronly: false,
+            id,
+            permissions: None,
+            project_active: None,
+            project_color: None,
+            project_id: None,
+            project_name: project.map(|x| x.to_string()),
+            server_deleted_at: None,
+            start,
+            stop: None,
+            tag_ids: Vec::new(),
+            tags: tags.to_vec(),
+            task_id: None,
+            task_name: None,
+            user_id: 0,
+            workspace_id: 0,
+        }
+    }
+
+    pub fn stop_at(&mut self, stop: DateTime<Local>) {
+        self.at = stop;
+        self.stop = Some(stop);
+        self.duration = (stop - self.start).num_seconds();
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct NewTimeEntry {
+    pub created_with: String,
+    pub description: String,
+    pub duration: i64,
+    pub project_id: Option<u64>,
+    pub start: DateTime<Utc>,
+    pub tags: Vec<String>,
+    pub workspace_id: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UpdateTimeEntry {
+    pub stop: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct User {
+    pub id: u64,
+    pub default_workspace_id: u64,
+    pub email: String,
+    pub fullname: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Workspace {
+    pub id: u64,
+    pub name: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Project {
+    pub id: u64,
+    pub workspace_id: u64,
+    pub client_id: Option<u64>,
+    pub name: String,
+    pub color: Option<String>,
+    #[serde(default)]
+    pub active: bool,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Client {
+    pub id: u64,
+    pub wid: u64,
+    pub name: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Task {
+    pub id: u64,
+    pub workspace_id: u64,
+    pub project_id: u64,
+    pub name: String,
+    pub estimated_seconds: Option<i64>,
+    #[serde(default)]
+    pub active: bool,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Tag {
+    pub id: u64,
+    pub workspace_id: u64,
+    pub name: String,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct Data<T> {
     pub data: T,
@@ -47,13 +163,182 @@ impl Toggl {
         }
     }
 
+    /// Sends an authenticated request, logging how long Toggl took.
+    fn send(&self, req: reqwest::RequestBuilder) -> Result<reqwest::Response, Error> {
+        let req = req.basic_auth(&self.token, Some("api_token")).build()?;
+        let (method, path) = (req.method().clone(), req.url().path().to_string());
+        let start = Instant::now();
+        let res = self.client.execute(req);
+        let elapsed = start.elapsed().as_millis();
+        match res {
+            Ok(ref res) => debug!("{} {} {} in {} ms", method, path, res.status(), elapsed),
+            Err(ref e) => warn!("{} {} failed in {} ms: {}", method, path, elapsed, e),
+        }
+        Ok(res?.error_for_status()?)
+    }
+
+    fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T, Error> {
+        let mut res = self.send(self.client.get(&format!("{}{}", API_URL, path)))?;
+        Ok(res.json::<T>()?)
+    }
+
     pub fn time_entries(&self) -> Result<Vec<TimeEntry>, Error> {
-        let mut res = self
-            .client
-            .get("https://api.track.toggl.com/api/v9/me/time_entries")
-            .basic_auth(&self.token, Some("api_token"))
-            .send()?;
-        let entries = res.json::<Vec<TimeEntry>>()?;
-        Ok(entries)
+        self.get("/me/time_entries")
+    }
+
+    pub fn time_entries_between(
+        &self,
+        start: DateTime<Local>,
+        end: DateTime<Local>,
+    ) -> Result<Vec<TimeEntry>, Error> {
+        let mut res = self.send(
+            self.client
+                .get(&format!("{}/me/time_entries", API_URL))
+                .query(&[
+                    ("start_date", start.to_rfc3339()),
+                    ("end_date", end.to_rfc3339()),
+                ]),
+        )?;
+        Ok(res.json::<Vec<TimeEntry>>()?)
+    }
+
+    pub fn current_time_entry(&self) -> Result<Option<TimeEntry>, Error> {
+        self.get("/me/time_entries/current")
+    }
+
+    pub fn me(&self) -> Result<User, Error> {
+        self.get("/me")
+    }
+
+    pub fn workspaces(&self) -> Result<Vec<Workspace>, Error> {
+        self.get("/me/workspaces")
+    }
+
+    pub fn projects(&self) -> Result<Vec<Project>, Error> {
+        Ok(self
+            .get::<Option<Vec<Project>>>("/me/projects")?
+            .unwrap_or_default())
+    }
+
+    pub fn clients(&self) -> Result<Vec<Client>, Error> {
+        Ok(self
+            .get::<Option<Vec<Client>>>("/me/clients")?
+            .unwrap_or_default())
+    }
+
+    pub fn tasks(&self) -> Result<Vec<Task>, Error> {
+        Ok(self
+            .get::<Option<Vec<Task>>>("/me/tasks")?
+            .unwrap_or_default())
+    }
+
+    pub fn tags(&self) -> Result<Vec<Tag>, Error> {
+        Ok(self
+            .get::<Option<Vec<Tag>>>("/me/tags")?
+            .unwrap_or_default())
+    }
+
+    pub fn create_time_entry(
+        &self,
+        workspace_id: u64,
+        description: &str,
+        project_id: Option<u64>,
+        tags: &[String],
+    ) -> Result<TimeEntry, Error> {
+        let entry = NewTimeEntry {
+            created_with: "toggdoro".to_string(),
+            description: description.to_string(),
+            duration: -1,
+            project_id,
+            start: Utc::now(),
+            tags: tags.to_vec(),
+            workspace_id,
+        };
+        let mut res = self.send(
+            self.client
+                .post(&format!(
+                    "{}/workspaces/{}/time_entries",
+                    API_URL, workspace_id
+                ))
+                .json(&entry),
+        )?;
+        Ok(res.json::<TimeEntry>()?)
+    }
+
+    /// Stops the entry now, or backdated to `at` by updating its stop time.
+    pub fn stop_time_entry(
+        &self,
+        entry: &TimeEntry,
+        at: Option<DateTime<Local>>,
+    ) -> Result<TimeEntry, Error> {
+        let url = format!(
+            "{}/workspaces/{}/time_entries/{}",
+            API_URL, entry.workspace_id, entry.id
+        );
+        let req = match at {
+            Some(stop) => self.client.put(&url).json(&UpdateTimeEntry {
+                stop: stop.with_timezone(&Utc),
+            }),
+            None => self.client.patch(&format!("{}/stop", url)),
+        };
+        let mut res = self.send(req)?;
+        Ok(res.json::<TimeEntry>()?)
+    }
+}
+
+impl TimeTracker for Toggl {
+    fn time_entries(&self) -> Result<Vec<TimeEntry>, Error> {
+        Toggl::time_entries(self)
+    }
+
+    fn time_entries_between(
+        &self,
+        start: DateTime<Local>,
+        end: DateTime<Local>,
+    ) -> Result<Vec<TimeEntry>, Error> {
+        Toggl::time_entries_between(self, start, end)
+    }
+
+    fn current_time_entry(&self) -> Result<Option<TimeEntry>, Error> {
+        Toggl::current_time_entry(self)
+    }
+
+    /// Resolves the project and tags through the catalog, so names are
+    /// matched case-insensitively.
+    fn start(
+        &self,
+        catalog: &Catalog,
+        description: &str,
+        project: Option<&str>,
+        tags: &[String],
+    ) -> Result<TimeEntry, Error> {
+        let (workspace_id, project_id) = match project {
+            Some(name) => {
+                let p = catalog
+                    .find_project(name)
+                    .ok_or_else(|| format_err!("unknown project: {}", name))?;
+                (p.workspace_id, Some(p.id))
+            }
+            None => (
+                catalog
+                    .default_workspace_id
+                    .ok_or_else(|| format_err!("no default workspace"))?,
+                None,
+            ),
+        };
+        let tags: Vec<String> = tags
+            .iter()
+            .map(|x| catalog.tag_name(workspace_id, x))
+            .collect();
+
+        self.create_time_entry(workspace_id, description, project_id, &tags)
+    }
+
+    fn stop(&self, entry: &TimeEntry, at: Option<DateTime<Local>>) -> Result<TimeEntry, Error> {
+        self.stop_time_entry(entry, at)
+    }
+
+    fn fetch_catalog(&self) -> CatalogUpdate {
+        Catalog::fetch(self)
     }
 }
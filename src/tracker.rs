@@ -0,0 +1,98 @@
+use std::fmt;
+
+use chrono::{DateTime, Local};
+use failure::{Error, Fail};
+
+use crate::catalog::{Catalog, CatalogUpdate};
+use crate::config::{Config, TrackerConfig};
+use crate::metrics::InstrumentedTracker;
+use crate::toggl::{TimeEntry, Toggl};
+
+pub mod clockify;
+pub mod kimai;
+pub mod local;
+#[cfg(test)]
+mod mock;
+pub mod timewarrior;
+
+/// A failed request to the time tracker, as opposed to a bad command.
+#[derive(Debug)]
+pub struct TrackerError(pub Error);
+
+impl fmt::Display for TrackerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl Fail for TrackerError {
+    fn cause(&self) -> Option<&dyn Fail> {
+        Some(self.0.as_fail())
+    }
+}
+
+/// A time tracking service toggdoro reads entries from and controls.
+pub trait TimeTracker: Send + Sync {
+    /// Returns recent entries, newest first.
+    fn time_entries(&self) -> Result<Vec<TimeEntry>, Error>;
+
+    /// Returns entries started in `[start, end)`, newest first.
+    fn time_entries_between(
+        &self,
+        start: DateTime<Local>,
+        end: DateTime<Local>,
+    ) -> Result<Vec<TimeEntry>, Error>;
+
+    fn current_time_entry(&self) -> Result<Option<TimeEntry>, Error>;
+
+    /// Starts a new entry, stopping the running one if any.  Trackers that
+    /// need ids for the project and tags look them up in `catalog`.
+    fn start(
+        &self,
+        catalog: &Catalog,
+        description: &str,
+        project: Option<&str>,
+        tags: &[String],
+    ) -> Result<TimeEntry, Error>;
+
+    /// Stops the entry at the given time, or now.
+    fn stop(&self, entry: &TimeEntry, at: Option<DateTime<Local>>) -> Result<TimeEntry, Error>;
+
+    /// Fetches project, client and tag names for trackers whose entries
+    /// do not carry them.  Apply the update with `Catalog::apply`, so the
+    /// catalog is not locked during the requests.
+    fn fetch_catalog(&self) -> CatalogUpdate {
+        CatalogUpdate::default()
+    }
+}
+
+/// Returns the configured tracker, recording its requests in the metrics.
+pub fn from_config(config: &Config) -> Result<Box<dyn TimeTracker>, Error> {
+    let tracker: Box<dyn TimeTracker> = match config.tracker {
+        TrackerConfig::Toggl => Box::new(Toggl::new(config.toggl_token.to_string())),
+        TrackerConfig::Local { ref path } => Box::new(local::LocalTracker::new(path.as_deref())),
+        TrackerConfig::Timewarrior {
+            ref command,
+            ref project_prefix,
+        } => Box::new(timewarrior::Timewarrior::new(
+            command.as_deref(),
+            project_prefix.as_deref(),
+        )),
+        TrackerConfig::Clockify {
+            ref url,
+            ref token,
+            ref workspace_id,
+        } => Box::new(clockify::Clockify::new(
+            url.as_deref(),
+            token,
+            workspace_id.as_deref(),
+        )),
+        TrackerConfig::Kimai {
+            ref url,
+            ref token,
+            project_id,
+            activity_id,
+        } => Box::new(kimai::Kimai::new(url, token, project_id, activity_id)),
+    };
+    Ok(Box::new(InstrumentedTracker::new(tracker)))
+}
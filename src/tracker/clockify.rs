@@ -0,0 +1,345 @@
+use std::sync::Mutex;
+
+use chrono::{DateTime, Duration, Local, Utc};
+use failure::{format_err, Error};
+use serde::de::DeserializeOwned;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::catalog::Catalog;
+use crate::toggl::TimeEntry;
+use crate::tracker::TimeTracker;
+
+const API_URL: &str = "https://api.clockify.me/api/v1";
+
+/// Entries started within this many days are returned as recent ones.
+const RECENT_DAYS: i64 = 9;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct User {
+    id: String,
+    active_workspace: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Named {
+    id: String,
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Project {
+    name: String,
+    color: Option<String>,
+    client_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TimeInterval {
+    start: DateTime<Utc>,
+    end: Option<DateTime<Utc>>,
+}
+
+/// A hydrated Clockify time entry.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ClockifyEntry {
+    id: String,
+    #[serde(default)]
+    description: String,
+    project: Option<Project>,
+    tags: Option<Vec<Named>>,
+    time_interval: TimeInterval,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct NewEntry<'a> {
+    start: DateTime<Utc>,
+    description: &'a str,
+    project_id: Option<String>,
+    tag_ids: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct StopEntry {
+    end: DateTime<Utc>,
+}
+
+pub struct Clockify {
+    url: String,
+    token: String,
+    workspace_id: Option<String>,
+    client: reqwest::Client,
+    /// The user id and workspace id, looked up on first use.
+    ids: Mutex<Option<(String, String)>>,
+}
+
+/// Clockify ids are strings; entries are identified by their 64-bit FNV-1a
+/// hash, which stays the same across Rust releases unlike `DefaultHasher`.
+fn hash_id(id: &str) -> u64 {
+    id.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+impl ClockifyEntry {
+    fn into_entry(self) -> TimeEntry {
+        let start = self.time_interval.start.with_timezone(&Local);
+        let tags: Vec<String> = self
+            .tags
+            .unwrap_or_default()
+            .into_iter()
+            .map(|x| x.name)
+            .collect();
+        let mut entry = TimeEntry::new(
+            hash_id(&self.id),
+            &self.description,
+            self.project.as_ref().map(|x| x.name.as_str()),
+            &tags,
+            start,
+        );
+        if let Some(project) = self.project {
+            entry.project_color = project.color;
+            entry.client_name = project.client_name;
+        }
+        if let Some(end) = self.time_interval.end {
+            entry.stop_at(end.with_timezone(&Local));
+        }
+        entry
+    }
+}
+
+impl Clockify {
+    pub fn new(url: Option<&str>, token: &str, workspace_id: Option<&str>) -> Self {
+        Clockify {
+            url: url.unwrap_or(API_URL).trim_end_matches('/').to_string(),
+            token: token.to_string(),
+            workspace_id: workspace_id.map(|x| x.to_string()),
+            client: reqwest::Client::new(),
+            ids: Mutex::new(None),
+        }
+    }
+
+    fn get<T: DeserializeOwned>(&self, path: &str, query: &[(&str, String)]) -> Result<T, Error> {
+        let mut res = self
+            .client
+            .get(&format!("{}{}", self.url, path))
+            .header("X-Api-Key", self.token.as_str())
+            .query(query)
+            .send()?
+            .error_for_status()?;
+        Ok(res.json::<T>()?)
+    }
+
+    /// Returns the user id and the workspace id.
+    fn ids(&self) -> Result<(String, String), Error> {
+        let mut ids = self.ids.lock().unwrap();
+        if ids.is_none() {
+            let user: User = self.get("/user", &[])?;
+            let workspace_id = self.workspace_id.clone().unwrap_or(user.active_workspace);
+            *ids = Some((user.id, workspace_id));
+        }
+        Ok(ids.clone().unwrap())
+    }
+
+    fn entries(&self, query: &[(&str, String)]) -> Result<Vec<TimeEntry>, Error> {
+        let (user_id, workspace_id) = self.ids()?;
+        let mut query = query.to_vec();
+        query.push(("hydrated", "true".to_string()));
+        query.push(("page-size", "1000".to_string()));
+        let entries: Vec<ClockifyEntry> = self.get(
+            &format!("/workspaces/{}/user/{}/time-entries", workspace_id, user_id),
+            &query,
+        )?;
+        Ok(entries.into_iter().map(|x| x.into_entry()).collect())
+    }
+
+    /// Looks up the id of a project or tag by name, ignoring case.
+    fn find_id(&self, kind: &str, name: &str) -> Result<String, Error> {
+        let (_, workspace_id) = self.ids()?;
+        let items: Vec<Named> = self.get(
+            &format!("/workspaces/{}/{}", workspace_id, kind),
+            &[("name", name.to_string())],
+        )?;
+        let lower = name.to_lowercase();
+        items
+            .into_iter()
+            .find(|x| x.name.to_lowercase() == lower)
+            .map(|x| x.id)
+            .ok_or_else(|| format_err!("unknown {}: {}", kind.trim_end_matches('s'), name))
+    }
+}
+
+impl TimeTracker for Clockify {
+    fn time_entries(&self) -> Result<Vec<TimeEntry>, Error> {
+        let since = Utc::now() - Duration::days(RECENT_DAYS);
+        self.entries(&[("start", since.format("%Y-%m-%dT%H:%M:%SZ").to_string())])
+    }
+
+    fn time_entries_between(
+        &self,
+        start: DateTime<Local>,
+        end: DateTime<Local>,
+    ) -> Result<Vec<TimeEntry>, Error> {
+        let format = |t: DateTime<Local>| {
+            t.with_timezone(&Utc)
+                .format("%Y-%m-%dT%H:%M:%SZ")
+                .to_string()
+        };
+        self.entries(&[("start", format(start)), ("end", format(end))])
+    }
+
+    fn current_time_entry(&self) -> Result<Option<TimeEntry>, Error> {
+        Ok(self
+            .entries(&[("in-progress", "true".to_string())])?
+            .into_iter()
+            .next())
+    }
+
+    fn start(
+        &self,
+        _catalog: &Catalog,
+        description: &str,
+        project: Option<&str>,
+        tags: &[String],
+    ) -> Result<TimeEntry, Error> {
+        let (_, workspace_id) = self.ids()?;
+        let entry = NewEntry {
+            start: Utc::now(),
+            description,
+            project_id: project.map(|x| self.find_id("projects", x)).transpose()?,
+            tag_ids: tags
+                .iter()
+                .map(|x| self.find_id("tags", x))
+                .collect::<Result<_, _>>()?,
+        };
+        self.client
+            .post(&format!(
+                "{}/workspaces/{}/time-entries",
+                self.url, workspace_id
+            ))
+            .header("X-Api-Key", self.token.as_str())
+            .json(&entry)
+            .send()?
+            .error_for_status()?;
+        self.current_time_entry()?
+            .ok_or_else(|| format_err!("Clockify did not start an entry"))
+    }
+
+    fn stop(&self, entry: &TimeEntry, at: Option<DateTime<Local>>) -> Result<TimeEntry, Error> {
+        let (user_id, workspace_id) = self.ids()?;
+        let end = at.map_or_else(Utc::now, |x| x.with_timezone(&Utc));
+        self.client
+            .patch(&format!(
+                "{}/workspaces/{}/user/{}/time-entries",
+                self.url, workspace_id, user_id
+            ))
+            .header("X-Api-Key", self.token.as_str())
+            .json(&StopEntry { end })
+            .send()?
+            .error_for_status()?;
+        let mut stopped = entry.clone();
+        stopped.stop_at(end.with_timezone(&Local));
+        Ok(stopped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tracker::mock::MockServer;
+
+    const USER: &str = r##"{"id":"u1","email":"ann@example.com","name":"Ann","activeWorkspace":"w1","defaultWorkspace":"w1"}"##;
+
+    const ENTRIES: &str = r##"[
+        {"id":"6650d3a1e4b0","description":"write","userId":"u1","workspaceId":"w1",
+         "project":{"id":"p1","name":"Book","color":"#03A9F4","clientName":"Acme"},
+         "tags":[{"id":"t1","name":"draft"}],
+         "timeInterval":{"start":"2024-05-24T09:00:00Z","end":null,"duration":null}},
+        {"id":"6650d0b2e4b0","description":"Pomodoro Break","userId":"u1","workspaceId":"w1",
+         "project":null,"tags":null,
+         "timeInterval":{"start":"2024-05-24T08:55:00Z","end":"2024-05-24T09:00:00Z","duration":"PT5M"}}
+    ]"##;
+
+    const RUNNING: &str = r##"[
+        {"id":"6650d3a1e4b0","description":"write","userId":"u1","workspaceId":"w1",
+         "project":{"id":"p1","name":"Book","color":"#03A9F4","clientName":"Acme"},
+         "tags":[{"id":"t1","name":"draft"}],
+         "timeInterval":{"start":"2024-05-24T09:00:00Z","end":null,"duration":null}}
+    ]"##;
+
+    #[test]
+    fn hash_id_is_fnv_1a() {
+        assert_eq!(hash_id(""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(hash_id("a"), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn reads_entries() {
+        let server = MockServer::start(vec![
+            ("GET", "/user", USER),
+            ("GET", "/workspaces/w1/user/u1/time-entries", ENTRIES),
+        ]);
+        let clockify = Clockify::new(Some(&server.url), "key", None);
+
+        let entries = clockify.time_entries().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].id, hash_id("6650d3a1e4b0"));
+        assert_eq!(entries[0].description, "write");
+        assert_eq!(entries[0].project_name.as_deref(), Some("Book"));
+        assert_eq!(entries[0].client_name.as_deref(), Some("Acme"));
+        assert_eq!(entries[0].tags, vec!["draft"]);
+        assert!(entries[0].duration < 0);
+        assert_eq!(entries[1].duration, 300);
+        assert!(entries[1].tags.is_empty());
+
+        let requests = server.requests.lock().unwrap();
+        assert_eq!(requests[0].header("X-Api-Key"), Some("key"));
+    }
+
+    #[test]
+    fn starts_and_stops() {
+        let server = MockServer::start(vec![
+            ("GET", "/user", USER),
+            (
+                "GET",
+                "/workspaces/w1/projects",
+                r##"[{"id":"p1","name":"Book"}]"##,
+            ),
+            (
+                "GET",
+                "/workspaces/w1/tags",
+                r##"[{"id":"t2","name":"drafts"},{"id":"t1","name":"Draft"}]"##,
+            ),
+            ("POST", "/workspaces/w1/time-entries", "{}"),
+            ("GET", "/workspaces/w1/user/u1/time-entries", RUNNING),
+            ("PATCH", "/workspaces/w1/user/u1/time-entries", "{}"),
+        ]);
+        let clockify = Clockify::new(Some(&format!("{}/", server.url)), "key", None);
+
+        let entry = clockify
+            .start(
+                &Catalog::default(),
+                "write",
+                Some("book"),
+                &["draft".to_string()],
+            )
+            .unwrap();
+        assert_eq!(entry.description, "write");
+        let at = entry.start + Duration::minutes(25);
+        let stopped = clockify.stop(&entry, Some(at)).unwrap();
+        assert_eq!(stopped.duration, 25 * 60);
+
+        let requests = server.requests.lock().unwrap();
+        let post = requests.iter().find(|x| x.method == "POST").unwrap();
+        let body: serde_json::Value = serde_json::from_str(&post.body).unwrap();
+        assert_eq!(body["description"], "write");
+        assert_eq!(body["projectId"], "p1");
+        assert_eq!(body["tagIds"], serde_json::json!(["t1"]));
+        let patch = requests.iter().find(|x| x.method == "PATCH").unwrap();
+        let body: serde_json::Value = serde_json::from_str(&patch.body).unwrap();
+        assert_eq!(body["end"], "2024-05-24T09:25:00Z");
+    }
+}
@@ -0,0 +1,315 @@
+use chrono::{DateTime, Duration, FixedOffset, Local};
+use failure::{format_err, Error};
+use serde::de::DeserializeOwned;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::catalog::Catalog;
+use crate::toggl::TimeEntry;
+use crate::tracker::TimeTracker;
+
+/// Entries started within this many days are returned as recent ones.
+const RECENT_DAYS: i64 = 9;
+
+/// Kimai accepts HTML5 local date-times in queries and bodies.
+const TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+mod datetime {
+    use chrono::{DateTime, FixedOffset};
+    use serde::{de, Deserialize, Deserializer};
+
+    /// Parses Kimai's `2024-05-01T08:00:00+0200`.
+    pub fn deserialize<'de, D>(d: D) -> Result<Option<DateTime<FixedOffset>>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match Option::<String>::deserialize(d)? {
+            Some(s) => DateTime::parse_from_str(&s, "%Y-%m-%dT%H:%M:%S%z")
+                .map(Some)
+                .map_err(de::Error::custom),
+            None => Ok(None),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Customer {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Project {
+    id: u64,
+    name: String,
+    color: Option<String>,
+    customer: Option<Customer>,
+}
+
+/// A Kimai timesheet fetched with `full=true`.
+#[derive(Debug, Deserialize)]
+struct Timesheet {
+    id: u64,
+    #[serde(with = "datetime")]
+    begin: Option<DateTime<FixedOffset>>,
+    #[serde(default, with = "datetime")]
+    end: Option<DateTime<FixedOffset>>,
+    description: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    project: Option<Project>,
+}
+
+#[derive(Debug, Serialize)]
+struct NewTimesheet<'a> {
+    begin: String,
+    project: u64,
+    activity: u64,
+    description: &'a str,
+    tags: String,
+}
+
+#[derive(Debug, Serialize)]
+struct UpdateTimesheet {
+    end: String,
+}
+
+pub struct Kimai {
+    url: String,
+    token: String,
+    project_id: Option<u64>,
+    activity_id: Option<u64>,
+    client: reqwest::Client,
+}
+
+impl Timesheet {
+    fn into_entry(self) -> Result<TimeEntry, Error> {
+        let start = self
+            .begin
+            .ok_or_else(|| format_err!("timesheet {} has no begin", self.id))?
+            .with_timezone(&Local);
+        let mut entry = TimeEntry::new(
+            self.id,
+            self.description.as_deref().unwrap_or(""),
+            self.project.as_ref().map(|x| x.name.as_str()),
+            &self.tags,
+            start,
+        );
+        if let Some(project) = self.project {
+            entry.project_id = Some(project.id);
+            entry.project_color = project.color;
+            entry.client_name = project.customer.map(|x| x.name);
+        }
+        if let Some(end) = self.end {
+            entry.stop_at(end.with_timezone(&Local));
+        }
+        Ok(entry)
+    }
+}
+
+impl Kimai {
+    pub fn new(url: &str, token: &str, project_id: Option<u64>, activity_id: Option<u64>) -> Self {
+        Kimai {
+            url: url.trim_end_matches('/').to_string(),
+            token: token.to_string(),
+            project_id,
+            activity_id,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    fn get<T: DeserializeOwned>(&self, path: &str, query: &[(&str, String)]) -> Result<T, Error> {
+        let mut res = self
+            .client
+            .get(&format!("{}/api{}", self.url, path))
+            .bearer_auth(&self.token)
+            .query(query)
+            .send()?
+            .error_for_status()?;
+        Ok(res.json::<T>()?)
+    }
+
+    fn timesheets(&self, query: &[(&str, String)]) -> Result<Vec<TimeEntry>, Error> {
+        let mut query = query.to_vec();
+        query.push(("full", "true".to_string()));
+        query.push(("order", "DESC".to_string()));
+        query.push(("size", "1000".to_string()));
+        let timesheets: Vec<Timesheet> = self.get("/timesheets", &query)?;
+        timesheets.into_iter().map(|x| x.into_entry()).collect()
+    }
+
+    /// Looks up the id of a project by name, ignoring case.
+    fn find_project(&self, name: &str) -> Result<u64, Error> {
+        let projects: Vec<Project> = self.get("/projects", &[("term", name.to_string())])?;
+        let lower = name.to_lowercase();
+        projects
+            .into_iter()
+            .find(|x| x.name.to_lowercase() == lower)
+            .map(|x| x.id)
+            .ok_or_else(|| format_err!("unknown project: {}", name))
+    }
+}
+
+impl TimeTracker for Kimai {
+    fn time_entries(&self) -> Result<Vec<TimeEntry>, Error> {
+        let since = Local::now() - Duration::days(RECENT_DAYS);
+        self.timesheets(&[("begin", since.format(TIME_FORMAT).to_string())])
+    }
+
+    fn time_entries_between(
+        &self,
+        start: DateTime<Local>,
+        end: DateTime<Local>,
+    ) -> Result<Vec<TimeEntry>, Error> {
+        self.timesheets(&[
+            ("begin", start.format(TIME_FORMAT).to_string()),
+            ("end", end.format(TIME_FORMAT).to_string()),
+        ])
+    }
+
+    fn current_time_entry(&self) -> Result<Option<TimeEntry>, Error> {
+        let timesheets: Vec<Timesheet> =
+            self.get("/timesheets/active", &[("full", "true".to_string())])?;
+        timesheets
+            .into_iter()
+            .next()
+            .map(|x| x.into_entry())
+            .transpose()
+    }
+
+    fn start(
+        &self,
+        _catalog: &Catalog,
+        description: &str,
+        project: Option<&str>,
+        tags: &[String],
+    ) -> Result<TimeEntry, Error> {
+        let project = match project {
+            Some(name) => self.find_project(name)?,
+            None => self
+                .project_id
+                .ok_or_else(|| format_err!("no project given and no default project_id"))?,
+        };
+        let timesheet = NewTimesheet {
+            begin: Local::now().format(TIME_FORMAT).to_string(),
+            project,
+            activity: self
+                .activity_id
+                .ok_or_else(|| format_err!("activity_id is not configured"))?,
+            description,
+            tags: tags.join(","),
+        };
+        let mut res = self
+            .client
+            .post(&format!("{}/api/timesheets", self.url))
+            .bearer_auth(&self.token)
+            .query(&[("full", "true")])
+            .json(&timesheet)
+            .send()?
+            .error_for_status()?;
+        res.json::<Timesheet>()?.into_entry()
+    }
+
+    fn stop(&self, entry: &TimeEntry, at: Option<DateTime<Local>>) -> Result<TimeEntry, Error> {
+        let req = match at {
+            Some(end) => self
+                .client
+                .patch(&format!("{}/api/timesheets/{}", self.url, entry.id))
+                .json(&UpdateTimesheet {
+                    end: end.format(TIME_FORMAT).to_string(),
+                }),
+            None => self
+                .client
+                .patch(&format!("{}/api/timesheets/{}/stop", self.url, entry.id)),
+        };
+        let mut res = req
+            .bearer_auth(&self.token)
+            .query(&[("full", "true")])
+            .send()?
+            .error_for_status()?;
+        res.json::<Timesheet>()?.into_entry()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tracker::mock::MockServer;
+
+    const TIMESHEETS: &str = r##"[
+        {"id":42,"begin":"2024-05-24T11:00:00+0200","end":null,"duration":0,
+         "description":"write","tags":["draft"],"user":1,"activity":{"id":3,"name":"Writing"},
+         "project":{"id":7,"name":"Book","color":"#03a9f4","customer":{"id":2,"name":"Acme"}}},
+        {"id":41,"begin":"2024-05-24T10:55:00+0200","end":"2024-05-24T11:00:00+0200","duration":300,
+         "description":null,"tags":[],"user":1,"activity":{"id":3,"name":"Writing"},"project":null}
+    ]"##;
+
+    const STARTED: &str = r##"{"id":43,"begin":"2024-05-24T11:30:00+0200","end":null,
+        "description":"write","tags":["draft"],"project":{"id":7,"name":"Book","color":null,"customer":null}}"##;
+
+    const STOPPED: &str = r##"{"id":43,"begin":"2024-05-24T11:30:00+0200","end":"2024-05-24T11:55:00+0200",
+        "description":"write","tags":["draft"],"project":{"id":7,"name":"Book","color":null,"customer":null}}"##;
+
+    #[test]
+    fn reads_timesheets() {
+        let server = MockServer::start(vec![
+            ("GET", "/api/timesheets/active", "[]"),
+            ("GET", "/api/timesheets", TIMESHEETS),
+        ]);
+        let kimai = Kimai::new(&server.url, "secret", None, None);
+
+        let entries = kimai.time_entries().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].id, 42);
+        assert_eq!(entries[0].project_id, Some(7));
+        assert_eq!(entries[0].project_name.as_deref(), Some("Book"));
+        assert_eq!(entries[0].client_name.as_deref(), Some("Acme"));
+        assert_eq!(entries[0].tags, vec!["draft"]);
+        assert!(entries[0].duration < 0);
+        assert_eq!(entries[1].description, "");
+        assert_eq!(entries[1].duration, 300);
+        assert!(kimai.current_time_entry().unwrap().is_none());
+
+        let requests = server.requests.lock().unwrap();
+        assert_eq!(requests[0].header("Authorization"), Some("Bearer secret"));
+    }
+
+    #[test]
+    fn starts_and_stops() {
+        let server = MockServer::start(vec![
+            (
+                "GET",
+                "/api/projects",
+                r##"[{"id":7,"name":"Book","color":null,"customer":null}]"##,
+            ),
+            ("POST", "/api/timesheets", STARTED),
+            ("PATCH", "/api/timesheets/43/stop", STOPPED),
+        ]);
+        let kimai = Kimai::new(&server.url, "secret", None, Some(3));
+
+        let entry = kimai
+            .start(
+                &Catalog::default(),
+                "write",
+                Some("book"),
+                &["draft".to_string()],
+            )
+            .unwrap();
+        assert_eq!(entry.id, 43);
+        assert!(entry.duration < 0);
+        let stopped = kimai.stop(&entry, None).unwrap();
+        assert_eq!(stopped.duration, 25 * 60);
+
+        assert_eq!(
+            server.calls(),
+            vec![
+                "GET /api/projects",
+                "POST /api/timesheets",
+                "PATCH /api/timesheets/43/stop"
+            ]
+        );
+        let requests = server.requests.lock().unwrap();
+        let body: serde_json::Value = serde_json::from_str(&requests[1].body).unwrap();
+        assert_eq!(body["project"], 7);
+        assert_eq!(body["activity"], 3);
+        assert_eq!(body["tags"], "draft");
+    }
+}
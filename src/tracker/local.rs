@@ -0,0 +1,106 @@
+use std::fs::{self, File};
+use std::io::{BufReader, Write};
+use std::path::PathBuf;
+
+use chrono::{DateTime, Duration, Local};
+use failure::{format_err, Error};
+
+use crate::catalog::Catalog;
+use crate::config;
+use crate::toggl::TimeEntry;
+use crate::tracker::TimeTracker;
+
+/// Entries started within this many days are returned as recent ones.
+const RECENT_DAYS: i64 = 9;
+
+/// Keeps entries in a JSON file, so toggdoro works without any account.
+pub struct LocalTracker {
+    path: PathBuf,
+}
+
+impl LocalTracker {
+    pub fn new(path: Option<&str>) -> Self {
+        LocalTracker {
+            path: path
+                .map(PathBuf::from)
+                .unwrap_or_else(|| config::data_dir().join("entries.json")),
+        }
+    }
+
+    /// Loads all entries, newest first.
+    fn load(&self) -> Result<Vec<TimeEntry>, Error> {
+        match File::open(&self.path) {
+            Ok(file) => Ok(serde_json::from_reader(BufReader::new(file))?),
+            Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Saves entries through a temporary file so readers never see a
+    /// partially written one.
+    fn save(&self, entries: &[TimeEntry]) -> Result<(), Error> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        let mut file = File::create(&tmp)?;
+        file.write_all(serde_json::to_string(entries)?.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+impl TimeTracker for LocalTracker {
+    fn time_entries(&self) -> Result<Vec<TimeEntry>, Error> {
+        let since = Local::now() - Duration::days(RECENT_DAYS);
+        let mut entries = self.load()?;
+        entries.retain(|x| x.start >= since);
+        Ok(entries)
+    }
+
+    fn time_entries_between(
+        &self,
+        start: DateTime<Local>,
+        end: DateTime<Local>,
+    ) -> Result<Vec<TimeEntry>, Error> {
+        let mut entries = self.load()?;
+        entries.retain(|x| x.start >= start && x.start < end);
+        Ok(entries)
+    }
+
+    fn current_time_entry(&self) -> Result<Option<TimeEntry>, Error> {
+        Ok(self.load()?.into_iter().find(|x| x.duration < 0))
+    }
+
+    fn start(
+        &self,
+        _catalog: &Catalog,
+        description: &str,
+        project: Option<&str>,
+        tags: &[String],
+    ) -> Result<TimeEntry, Error> {
+        let mut entries = self.load()?;
+        let now = Local::now();
+        for x in entries.iter_mut().filter(|x| x.duration < 0) {
+            x.stop_at(now);
+        }
+        let id = entries.iter().map(|x| x.id).max().unwrap_or(0) + 1;
+        let entry = TimeEntry::new(id, description, project, tags, now);
+        entries.insert(0, entry.clone());
+        self.save(&entries)?;
+        Ok(entry)
+    }
+
+    fn stop(&self, entry: &TimeEntry, at: Option<DateTime<Local>>) -> Result<TimeEntry, Error> {
+        let mut entries = self.load()?;
+        let x = entries
+            .iter_mut()
+            .find(|x| x.id == entry.id)
+            .ok_or_else(|| format_err!("no such entry: {}", entry.id))?;
+        x.stop_at(at.unwrap_or_else(Local::now));
+        let stopped = x.clone();
+        self.save(&entries)?;
+        Ok(stopped)
+    }
+}
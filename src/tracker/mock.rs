@@ -0,0 +1,57 @@
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::http::{self, Request};
+
+/// A canned response: method, path prefix including any query, and body.
+pub type Route = (&'static str, &'static str, &'static str);
+
+/// A local HTTP server replaying recorded responses to tracker clients.
+pub struct MockServer {
+    pub url: String,
+    /// Requests received so far, oldest first.
+    pub requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl MockServer {
+    /// Answers each request with the first route whose method matches and
+    /// whose path is a prefix of the request's, or 404.
+    pub fn start(routes: Vec<Route>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let received = requests.clone();
+        thread::spawn(move || {
+            for mut stream in listener.incoming().filter_map(Result::ok) {
+                let req = match http::read_request(&stream) {
+                    Ok(req) => req,
+                    Err(_) => continue,
+                };
+                let route = routes
+                    .iter()
+                    .find(|(method, path, _)| req.method == *method && req.path.starts_with(path));
+                // Record the request before answering, so the client sees it
+                // in `requests` as soon as it has the response.
+                received.lock().unwrap().push(req);
+                let _ = match route {
+                    Some((_, _, body)) => {
+                        http::respond(&mut stream, "200 OK", "application/json", body.as_bytes())
+                    }
+                    None => http::respond(&mut stream, "404 Not Found", "text/plain", b""),
+                };
+            }
+        });
+        MockServer { url, requests }
+    }
+
+    /// Returns the method and path of the requests received so far.
+    pub fn calls(&self) -> Vec<String> {
+        self.requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|x| format!("{} {}", x.method, x.split_path().0))
+            .collect()
+    }
+}
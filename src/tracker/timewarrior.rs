@@ -0,0 +1,222 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+use chrono::{DateTime, Duration, Local, NaiveDateTime, TimeZone, Utc};
+use failure::{format_err, Error};
+use serde_derive::Deserialize;
+
+use crate::catalog::Catalog;
+use crate::toggl::TimeEntry;
+use crate::tracker::TimeTracker;
+
+/// Intervals started within this many days are returned as recent ones.
+const RECENT_DAYS: i64 = 9;
+
+/// Ids are made from the start time in seconds, times this, plus the
+/// number of earlier intervals started in the same second.
+const IDS_PER_SECOND: u64 = 100;
+
+/// An interval as printed by `timew export`.
+#[derive(Debug, Deserialize)]
+struct Interval {
+    start: String,
+    end: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    annotation: Option<String>,
+}
+
+/// Reads and controls Timewarrior through the `timew` command.  The
+/// annotation becomes the description, a tag with the project prefix the
+/// project, and the remaining tags the tags.
+pub struct Timewarrior {
+    command: String,
+    project_prefix: String,
+    /// Database passed to `timew` as `TIMEWARRIORDB`, if not the default.
+    db: Option<PathBuf>,
+}
+
+fn parse_time(s: &str) -> Result<DateTime<Local>, Error> {
+    let t = NaiveDateTime::parse_from_str(s, "%Y%m%dT%H%M%SZ")?;
+    Ok(Utc.from_utc_datetime(&t).with_timezone(&Local))
+}
+
+fn format_time(t: &DateTime<Local>) -> String {
+    t.format("%Y-%m-%dT%H:%M:%S").to_string()
+}
+
+impl Timewarrior {
+    pub fn new(command: Option<&str>, project_prefix: Option<&str>) -> Self {
+        Timewarrior {
+            command: command.unwrap_or("timew").to_string(),
+            project_prefix: project_prefix.unwrap_or("project:").to_string(),
+            db: None,
+        }
+    }
+
+    fn run(&self, args: &[&str]) -> Result<String, Error> {
+        let mut command = Command::new(&self.command);
+        if let Some(ref db) = self.db {
+            command.env("TIMEWARRIORDB", db);
+        }
+        let output = command.args(args).output()?;
+        if !output.status.success() {
+            return Err(format_err!(
+                "{} {} failed: {}",
+                self.command,
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(String::from_utf8(output.stdout)?)
+    }
+
+    /// Converts an interval; `nth` counts the earlier intervals started in
+    /// the same second, which would otherwise get the same id.
+    fn to_entry(&self, interval: Interval, nth: u64) -> Result<TimeEntry, Error> {
+        let start = parse_time(&interval.start)?;
+        let project = interval
+            .tags
+            .iter()
+            .find_map(|x| x.strip_prefix(self.project_prefix.as_str()));
+        let tags: Vec<String> = interval
+            .tags
+            .iter()
+            .filter(|x| !x.starts_with(self.project_prefix.as_str()))
+            .cloned()
+            .collect();
+
+        let mut entry = TimeEntry::new(
+            start.timestamp() as u64 * IDS_PER_SECOND + nth,
+            interval.annotation.as_deref().unwrap_or(""),
+            project,
+            &tags,
+            start,
+        );
+        if let Some(ref end) = interval.end {
+            entry.stop_at(parse_time(end)?);
+        }
+        Ok(entry)
+    }
+
+    /// Exports intervals in the given range, newest first.
+    fn export(&self, range: &[&str]) -> Result<Vec<TimeEntry>, Error> {
+        let mut args = vec!["export"];
+        args.extend_from_slice(range);
+        let intervals: Vec<Interval> = serde_json::from_str(&self.run(&args)?)?;
+        let mut entries: Vec<TimeEntry> = Vec::new();
+        for interval in intervals {
+            let start = parse_time(&interval.start)?;
+            let nth = entries
+                .iter()
+                .rev()
+                .take_while(|x| x.start.timestamp() == start.timestamp())
+                .count();
+            entries.push(self.to_entry(interval, nth as u64)?);
+        }
+        entries.reverse();
+        Ok(entries)
+    }
+}
+
+impl TimeTracker for Timewarrior {
+    fn time_entries(&self) -> Result<Vec<TimeEntry>, Error> {
+        let since = format_time(&(Local::now() - Duration::days(RECENT_DAYS)));
+        self.export(&["from", &since])
+    }
+
+    fn time_entries_between(
+        &self,
+        start: DateTime<Local>,
+        end: DateTime<Local>,
+    ) -> Result<Vec<TimeEntry>, Error> {
+        self.export(&[&format_time(&start), "-", &format_time(&end)])
+    }
+
+    fn current_time_entry(&self) -> Result<Option<TimeEntry>, Error> {
+        Ok(self.time_entries()?.into_iter().find(|x| x.duration < 0))
+    }
+
+    fn start(
+        &self,
+        _catalog: &Catalog,
+        description: &str,
+        project: Option<&str>,
+        tags: &[String],
+    ) -> Result<TimeEntry, Error> {
+        let project = project.map(|x| format!("{}{}", self.project_prefix, x));
+        let mut args = vec!["start"];
+        args.extend(project.iter().map(|x| x.as_str()));
+        args.extend(tags.iter().map(|x| x.as_str()));
+        args.push(":quiet");
+        self.run(&args)?;
+        if !description.is_empty() {
+            self.run(&["annotate", "@1", description, ":quiet"])?;
+        }
+        self.current_time_entry()?
+            .ok_or_else(|| format_err!("timew did not start an interval"))
+    }
+
+    fn stop(&self, entry: &TimeEntry, at: Option<DateTime<Local>>) -> Result<TimeEntry, Error> {
+        let at = at.unwrap_or_else(Local::now);
+        self.run(&["stop", &format_time(&at), ":quiet"])?;
+        let mut stopped = entry.clone();
+        stopped.stop_at(at);
+        Ok(stopped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, process};
+
+    #[test]
+    fn same_second_intervals_get_distinct_ids() {
+        let timew = Timewarrior::new(None, None);
+        let intervals: Vec<Interval> = serde_json::from_str(
+            r#"[{"start":"20240102T090000Z","end":"20240102T090000Z","tags":["project:book","draft"],"annotation":"write"},
+                {"start":"20240102T090000Z","tags":["pomodoro-break"]}]"#,
+        )
+        .unwrap();
+        let mut intervals = intervals.into_iter();
+        let first = timew.to_entry(intervals.next().unwrap(), 0).unwrap();
+        let second = timew.to_entry(intervals.next().unwrap(), 1).unwrap();
+
+        assert_eq!(first.description, "write");
+        assert_eq!(first.project_name.as_deref(), Some("book"));
+        assert_eq!(first.tags, vec!["draft"]);
+        assert_eq!(first.duration, 0);
+        assert_eq!(second.description, "");
+        assert_eq!(second.duration, -1);
+        assert_ne!(first.id, second.id);
+        assert_eq!(first.start, second.start);
+    }
+
+    /// Runs the real `timew` against a temporary database.
+    #[test]
+    #[ignore = "requires timew"]
+    fn start_and_stop_with_timew() {
+        let db = env::temp_dir().join(format!("toggdoro-timew-{}", process::id()));
+        fs::create_dir_all(db.join("data")).unwrap();
+        fs::write(db.join("timewarrior.cfg"), "").unwrap();
+        let mut timew = Timewarrior::new(None, None);
+        timew.db = Some(db.clone());
+
+        let catalog = Catalog::default();
+        let tags = vec!["draft".to_string()];
+        let entry = timew.start(&catalog, "write", Some("book"), &tags).unwrap();
+        assert_eq!(entry.description, "write");
+        assert_eq!(entry.project_name.as_deref(), Some("book"));
+        assert_eq!(entry.tags, tags);
+        assert!(entry.duration < 0);
+
+        let stopped = timew.stop(&entry, None).unwrap();
+        assert!(stopped.duration >= 0);
+        assert!(timew.current_time_entry().unwrap().is_none());
+        let entries = timew.time_entries().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].id, entry.id);
+        let _ = fs::remove_dir_all(db);
+    }
+}
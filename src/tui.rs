@@ -0,0 +1,362 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local};
+use failure::Error;
+use ratatui::backend::CrosstermBackend;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::crossterm::execute;
+use ratatui::crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use ratatui::layout::{Alignment, Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, Clear, Gauge, List, ListItem, ListState, Paragraph};
+use ratatui::{Frame, Terminal};
+
+use crate::client::request;
+use crate::pomodoro::PomodoroMode;
+use crate::status::{RecentTask, Status, Today};
+
+/// How often the daemon is polled for its status.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Today's history is reloaded at least this often, and whenever the phase
+/// changes.
+const TODAY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Minutes the `z` key snoozes the phase by.
+const SNOOZE_MIN: u32 = 5;
+
+/// Glyphs of the big countdown, five rows each.
+const DIGITS: [[&str; 5]; 12] = [
+    ["███", "█ █", "█ █", "█ █", "███"],
+    ["  █", "  █", "  █", "  █", "  █"],
+    ["███", "  █", "███", "█  ", "███"],
+    ["███", "  █", "███", "  █", "███"],
+    ["█ █", "█ █", "███", "  █", "  █"],
+    ["███", "█  ", "███", "  █", "███"],
+    ["███", "█  ", "███", "█ █", "███"],
+    ["███", "  █", "  █", "  █", "  █"],
+    ["███", "█ █", "███", "█ █", "███"],
+    ["███", "█ █", "███", "  █", "███"],
+    ["   ", " █ ", "   ", " █ ", "   "],
+    ["   ", "   ", "███", "   ", "   "],
+];
+
+fn big_text(s: &str) -> Vec<Line<'static>> {
+    (0..5)
+        .map(|row| {
+            let line: Vec<&str> = s
+                .chars()
+                .filter_map(|c| match c {
+                    '0'..='9' => Some(DIGITS[c as usize - '0' as usize][row]),
+                    ':' => Some(DIGITS[10][row]),
+                    '-' => Some(DIGITS[11][row]),
+                    _ => None,
+                })
+                .collect();
+            Line::from(line.join(" "))
+        })
+        .collect()
+}
+
+fn remaining(finish_time: DateTime<Local>, now: DateTime<Local>) -> String {
+    let secs = (finish_time - now).num_seconds();
+    let sign = if secs < 0 { "-" } else { "" };
+    format!("{}{:02}:{:02}", sign, secs.abs() / 60, secs.abs() % 60)
+}
+
+struct App {
+    socket: PathBuf,
+    status: Option<Status>,
+    today: Option<Today>,
+    /// Reply to the last command, or the last error.
+    message: String,
+    /// Recent tasks offered by the `s` key.
+    picker: Option<(Vec<RecentTask>, ListState)>,
+    today_loaded: Option<(Instant, String)>,
+}
+
+impl App {
+    fn refresh(&mut self) {
+        match request(&self.socket, "status json").and_then(|x| Ok(serde_json::from_str(&x)?)) {
+            Ok(status) => self.status = Some(status),
+            Err(e) => {
+                self.status = None;
+                self.message = e.to_string();
+                return;
+            }
+        }
+
+        let status = self.status.as_ref().unwrap();
+        let key = format!("{:?}/{}/{}", status.mode, status.count, status.description);
+        let stale = self
+            .today_loaded
+            .as_ref()
+            .is_none_or(|x| x.0.elapsed() >= TODAY_INTERVAL || x.1 != key);
+        if stale {
+            match request(&self.socket, "today").and_then(|x| Ok(serde_json::from_str(&x)?)) {
+                Ok(today) => self.today = Some(today),
+                Err(e) => self.message = e.to_string(),
+            }
+            self.today_loaded = Some((Instant::now(), key));
+        }
+    }
+
+    fn command(&mut self, command: &str) {
+        self.message = match request(&self.socket, command) {
+            Ok(reply) => reply,
+            Err(e) => format!("error: {}", e),
+        };
+        self.today_loaded = None;
+        self.refresh();
+    }
+
+    /// Handles a key press.  Returns false to quit.
+    fn on_key(&mut self, code: KeyCode) -> bool {
+        if let Some((ref tasks, ref mut state)) = self.picker {
+            let selected = state.selected().unwrap_or(0);
+            match code {
+                KeyCode::Up | KeyCode::Char('k') => {
+                    state.select(Some(selected.saturating_sub(1)));
+                }
+                KeyCode::Down | KeyCode::Char('j') => {
+                    state.select(Some((selected + 1).min(tasks.len().saturating_sub(1))));
+                }
+                KeyCode::Enter => {
+                    let command = tasks
+                        .get(selected)
+                        .map(|x| format!("start {}", x.to_start_args()));
+                    self.picker = None;
+                    if let Some(command) = command {
+                        self.command(&command);
+                    }
+                }
+                KeyCode::Esc | KeyCode::Char('q') => self.picker = None,
+                _ => (),
+            }
+            return true;
+        }
+
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Char('s') => {
+                match request(&self.socket, "recent")
+                    .and_then(|x| Ok(serde_json::from_str::<Vec<RecentTask>>(&x)?))
+                {
+                    Ok(tasks) if tasks.is_empty() => self.message = "no recent tasks".to_string(),
+                    Ok(tasks) => {
+                        self.picker = Some((tasks, ListState::default().with_selected(Some(0))))
+                    }
+                    Err(e) => self.message = format!("error: {}", e),
+                }
+            }
+            KeyCode::Char('n') => self.command("start next"),
+            KeyCode::Char('x') => self.command("stop"),
+            KeyCode::Char('b') => self.command("break"),
+            KeyCode::Char('k') => self.command("skip"),
+            KeyCode::Char('z') => self.command(&format!("snooze {}", SNOOZE_MIN)),
+            KeyCode::Char('p') => {
+                let paused = self.status.as_ref().is_some_and(|x| x.paused);
+                self.command(if paused { "resume" } else { "pause" });
+            }
+            _ => (),
+        }
+        true
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let now = Local::now();
+        let [header, countdown, gauge, task, today, footer] = Layout::vertical([
+            Constraint::Length(2),
+            Constraint::Length(6),
+            Constraint::Length(3),
+            Constraint::Length(3),
+            Constraint::Min(3),
+            Constraint::Length(2),
+        ])
+        .areas(frame.area());
+
+        let color = match self.status.as_ref().map(|x| x.mode) {
+            Some(PomodoroMode::Work) => Color::Red,
+            Some(PomodoroMode::Break) => Color::Green,
+            _ => Color::Gray,
+        };
+
+        let (cycle, time, ratio) = match self.status {
+            Some(ref s) if s.mode != PomodoroMode::Idle => {
+                let mut cycle = match s.mode {
+                    PomodoroMode::Work => format!(
+                        "Pomodoro {} of {} before the long break",
+                        s.count, s.long_break_after
+                    ),
+                    _ if s.count >= s.long_break_after => "Long break".to_string(),
+                    _ => format!("Short break after pomodoro {}", s.count),
+                };
+                if s.paused {
+                    cycle += " (paused)";
+                }
+                let finish_time = s.finish_time.unwrap_or(now);
+                let ratio = match s.phase_start {
+                    Some(start) if finish_time > start => {
+                        (now - start).num_seconds() as f64
+                            / (finish_time - start).num_seconds() as f64
+                    }
+                    _ => 0.0,
+                };
+                (cycle, remaining(finish_time, now), ratio.clamp(0.0, 1.0))
+            }
+            Some(_) => ("Idle".to_string(), "--:--".to_string(), 0.0),
+            None => ("Not connected".to_string(), "--:--".to_string(), 0.0),
+        };
+
+        frame.render_widget(
+            Paragraph::new(cycle)
+                .alignment(Alignment::Center)
+                .style(Style::default().fg(color).add_modifier(Modifier::BOLD)),
+            header,
+        );
+        frame.render_widget(
+            Paragraph::new(big_text(&time))
+                .alignment(Alignment::Center)
+                .style(Style::default().fg(color)),
+            countdown,
+        );
+        frame.render_widget(
+            Gauge::default()
+                .block(Block::default().borders(Borders::ALL))
+                .gauge_style(Style::default().fg(color))
+                .ratio(ratio),
+            gauge,
+        );
+
+        let mut lines = Vec::new();
+        if let Some(ref s) = self.status {
+            let mut description = s.description.clone();
+            if let (Some(done), Some(estimate)) = (s.task_done, s.task_estimate) {
+                description += &format!(" [{}/{}]", done, estimate);
+            }
+            lines.push(Line::from(description));
+            lines.push(Line::from(s.project.clone()).style(Style::default().fg(Color::DarkGray)));
+        }
+        frame.render_widget(Paragraph::new(lines).alignment(Alignment::Center), task);
+
+        let items: Vec<ListItem> = self
+            .today
+            .iter()
+            .flat_map(|x| {
+                let running = x.phase.iter().map(|r| (r, true));
+                running.chain(x.records.iter().rev().map(|r| (r, false)))
+            })
+            .map(|(r, running)| {
+                let outcome = if running {
+                    "running".to_string()
+                } else {
+                    format!("{:?}", r.outcome).to_lowercase()
+                };
+                ListItem::new(format!(
+                    "{}  {:<5} {:<11} {}",
+                    r.start.format("%H:%M"),
+                    format!("{:?}", r.mode),
+                    outcome,
+                    r.description
+                ))
+                .style(Style::default().fg(match r.mode {
+                    PomodoroMode::Work => Color::Red,
+                    _ => Color::Green,
+                }))
+            })
+            .collect();
+        frame.render_widget(
+            List::new(items).block(Block::default().borders(Borders::ALL).title("Today")),
+            today,
+        );
+
+        frame.render_widget(
+            Paragraph::new(vec![
+                Line::from(self.message.clone()),
+                Line::from("s start  n next  x stop  b break  k skip  z snooze  p pause  q quit")
+                    .style(Style::default().fg(Color::DarkGray)),
+            ]),
+            footer,
+        );
+
+        if let Some((ref tasks, ref mut state)) = self.picker {
+            let area = popup(frame.area());
+            let items: Vec<ListItem> = tasks
+                .iter()
+                .map(|x| ListItem::new(x.to_start_args()))
+                .collect();
+            frame.render_widget(Clear, area);
+            frame.render_stateful_widget(
+                List::new(items)
+                    .block(Block::default().borders(Borders::ALL).title("Start"))
+                    .highlight_style(Style::default().add_modifier(Modifier::REVERSED)),
+                area,
+                state,
+            );
+        }
+    }
+}
+
+fn popup(area: Rect) -> Rect {
+    let [_, area, _] = Layout::vertical([
+        Constraint::Percentage(20),
+        Constraint::Percentage(60),
+        Constraint::Percentage(20),
+    ])
+    .areas(area);
+    let [_, area, _] = Layout::horizontal([
+        Constraint::Percentage(15),
+        Constraint::Percentage(70),
+        Constraint::Percentage(15),
+    ])
+    .areas(area);
+    area
+}
+
+/// Restores the terminal however the UI exits.
+struct TerminalGuard;
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = disable_raw_mode();
+        let _ = execute!(io::stdout(), LeaveAlternateScreen);
+    }
+}
+
+/// Runs the full-screen UI against the daemon listening on `socket`.
+pub fn run(socket: &Path) -> Result<(), Error> {
+    enable_raw_mode()?;
+    let _guard = TerminalGuard;
+    execute!(io::stdout(), EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+
+    let mut app = App {
+        socket: socket.to_path_buf(),
+        status: None,
+        today: None,
+        message: String::new(),
+        picker: None,
+        today_loaded: None,
+    };
+    let mut last_poll: Option<Instant> = None;
+    loop {
+        if last_poll.is_none_or(|x| x.elapsed() >= POLL_INTERVAL) {
+            app.refresh();
+            last_poll = Some(Instant::now());
+        }
+        terminal.draw(|frame| app.draw(frame))?;
+        if event::poll(Duration::from_millis(250))? {
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press && !app.on_key(key.code) {
+                    break;
+                }
+            }
+        }
+    }
+    Ok(())
+}